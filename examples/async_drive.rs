@@ -0,0 +1,58 @@
+//! Drive forward with the async API: ramp the target speed through a sink
+//! while printing the reported velocity from a stream.
+//!
+//! ```shell
+//! cargo run --example async_drive -- ros2 "*"
+//! ```
+use autoware_manual_control::{
+    transport, AsyncManualController, ControlTarget, Mode, PublishPolicy,
+};
+use clap::ValueEnum;
+use futures::{stream, SinkExt, StreamExt};
+use std::time::Duration;
+use zenoh_ros_type::autoware_auto_vehicle_msgs::gear_command;
+
+#[tokio::main]
+async fn main() {
+    pretty_env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let mode = args
+        .next()
+        .map(|m| Mode::from_str(&m, true).unwrap())
+        .unwrap_or(Mode::DDS);
+    let prefix = transport::topic_prefix(args.next().as_deref());
+
+    let config = transport::load_config(None, &[]).unwrap();
+    let z_session = transport::open_session(config).unwrap();
+    let mut controller = AsyncManualController::new(z_session, mode, prefix).await;
+    controller.start(PublishPolicy::ExternalOnly).await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    if !controller.toggle_gate_mode().await {
+        controller.toggle_gate_mode().await;
+    }
+    controller.pub_gear_command(gear_command::DRIVE).await;
+
+    let velocity = controller.velocity_stream().await;
+    tokio::spawn(velocity.for_each(|report| async move {
+        println!("velocity(km/hr):{}", report.longitudinal_velocity * 3.6);
+    }));
+
+    // 0 => 20 km/hr in 1 km/hr steps every 500 ms
+    let ramp = stream::iter(0..=20).then(|kmh| async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        Ok(ControlTarget {
+            velocity: kmh as f32 / 3.6,
+            steering_tire_angle: 0.0,
+        })
+    });
+    let sink = controller.command_sink();
+    futures::pin_mut!(sink);
+    sink.send_all(&mut Box::pin(ramp)).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(5)).await;
+
+    controller.update_control_command(ControlTarget::default());
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    controller.stop().await;
+}
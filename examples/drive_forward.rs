@@ -0,0 +1,47 @@
+//! Switch to external mode, drive forward at 10 km/h for 10 seconds, then stop and park.
+//!
+//! ```shell
+//! cargo run --example drive_forward -- ros2 "*"
+//! ```
+use autoware_manual_control::{transport, ManualController, Mode};
+use clap::ValueEnum;
+use std::{thread, time::Duration};
+use zenoh_ros_type::autoware_auto_vehicle_msgs::gear_command;
+
+fn main() {
+    pretty_env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let mode = args
+        .next()
+        .map(|m| Mode::from_str(&m, true).unwrap())
+        .unwrap_or(Mode::DDS);
+    let prefix = transport::topic_prefix(args.next().as_deref());
+
+    let config = transport::load_config(None, &[]).unwrap();
+    let z_session = transport::open_session(config).unwrap();
+    let mut controller = ManualController::new(z_session, mode, prefix);
+    controller.init();
+    // Wait for the status subscribers to receive the current gate mode
+    thread::sleep(Duration::from_secs(1));
+
+    if !controller.toggle_gate_mode() {
+        // Already in external mode, so the toggle switched back to auto
+        controller.toggle_gate_mode();
+    }
+    controller.pub_gear_command(gear_command::DRIVE);
+
+    controller.update_control_command(10.0 / 3.6, 0.0);
+    for _ in 0..10 {
+        thread::sleep(Duration::from_secs(1));
+        println!("{}", controller.get_status());
+        println!("velocity(km/hr):{}", controller.current_velocity() * 3.6);
+    }
+
+    controller.update_control_command(0.0, 0.0);
+    while controller.current_velocity().abs() > 0.1 {
+        thread::sleep(Duration::from_millis(100));
+    }
+    controller.pub_gear_command(gear_command::PARK);
+    controller.stop();
+}
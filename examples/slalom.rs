@@ -0,0 +1,41 @@
+//! Weave left and right at a constant speed by sweeping the steering angle.
+//!
+//! Assume the vehicle is already in external mode and in DRIVE.
+//!
+//! ```shell
+//! cargo run --example slalom -- ros2 "*"
+//! ```
+use autoware_manual_control::{transport, ManualController, Mode};
+use clap::ValueEnum;
+use std::{f32::consts, thread, time::Duration};
+
+const SPEED: f32 = 2.778; // 10 km/hr
+const AMPLITUDE: f32 = 0.174; // 10 * (PI / 180)
+const PERIOD: f32 = 4.0; // second
+
+fn main() {
+    pretty_env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let mode = args
+        .next()
+        .map(|m| Mode::from_str(&m, true).unwrap())
+        .unwrap_or(Mode::DDS);
+    let prefix = transport::topic_prefix(args.next().as_deref());
+
+    let config = transport::load_config(None, &[]).unwrap();
+    let z_session = transport::open_session(config).unwrap();
+    let mut controller = ManualController::new(z_session, mode, prefix);
+    controller.init();
+
+    let step = 0.1; // second
+    for i in 0..(3.0 * PERIOD / step) as u32 {
+        let t = i as f32 * step;
+        let angle = AMPLITUDE * (2.0 * consts::PI * t / PERIOD).sin();
+        controller.update_control_command(SPEED, angle);
+        thread::sleep(Duration::from_secs_f32(step));
+    }
+    controller.update_control_command(0.0, 0.0);
+    thread::sleep(Duration::from_secs(3));
+    controller.stop();
+}
@@ -0,0 +1,77 @@
+use clap::ValueEnum;
+use serde_derive::{Deserialize, Serialize};
+use zenoh_ros_type::builtin_interfaces;
+
+/// How the controller hands the vehicle over between Autoware and itself
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum ControlApi {
+    /// `control/gate_mode_cmd` and the `api/autoware/set/engage` service
+    #[default]
+    Legacy,
+    /// The operation mode of the Autoware AD API, `api/operation_mode/*`
+    AdApi,
+}
+
+/// `autoware_adapi_v1_msgs/msg/OperationModeState` constants
+pub mod operation_mode {
+    pub const UNKNOWN: u8 = 0;
+    pub const STOP: u8 = 1;
+    pub const AUTONOMOUS: u8 = 2;
+    pub const LOCAL: u8 = 3;
+    pub const REMOTE: u8 = 4;
+}
+
+pub fn operation_mode_name(mode: u8) -> &'static str {
+    match mode {
+        operation_mode::STOP => "Stop",
+        operation_mode::AUTONOMOUS => "Autonomous",
+        operation_mode::LOCAL => "Local",
+        operation_mode::REMOTE => "Remote",
+        _ => "Unknown",
+    }
+}
+
+/// `autoware_adapi_v1_msgs/msg/OperationModeState`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OperationModeState {
+    pub stamp: builtin_interfaces::Time,
+    pub mode: u8,
+    pub is_autoware_control_enabled: bool,
+    pub is_in_transition: bool,
+    pub is_stop_mode_available: bool,
+    pub is_autonomous_mode_available: bool,
+    pub is_local_mode_available: bool,
+    pub is_remote_mode_available: bool,
+}
+
+/// `autoware_adapi_v1_msgs/msg/ResponseStatus`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResponseStatus {
+    pub success: bool,
+    pub code: u16,
+    pub message: String,
+}
+
+impl ResponseStatus {
+    /// The message and code of a failure as an error
+    pub fn into_result(self) -> Result<(), String> {
+        if self.success {
+            Ok(())
+        } else {
+            Err(format!("{} (code {})", self.message, self.code))
+        }
+    }
+}
+
+/// Request of the services without parameters, e.g. `ChangeOperationMode`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmptyRequest {
+    // ROS 2 pads empty messages with one byte
+    pub structure_needs_at_least_one_member: u8,
+}
+
+/// Response of the AD API services returning only a status, e.g. `ChangeOperationMode`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub status: ResponseStatus,
+}
@@ -0,0 +1,503 @@
+use cdr::{CdrLe, Infinite};
+use futures::{stream, Sink, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use zenoh::prelude::r#async::*;
+use zenoh::publication::Publisher;
+use zenoh_ros_type::{
+    autoware_auto_vehicle_msgs, builtin_interfaces, tier4_control_msgs, tier4_external_api_msgs,
+};
+
+use crate::control_loop::{ControlInput, ControlLoop, Handover, PublishPolicy, CONTROL_PERIOD};
+use crate::engage::{engage_result, EngageError, EngageResult};
+use crate::messages::{encode_control_command, MessageSet};
+use crate::service;
+use crate::settings::{Settings, TargetPolicy};
+use crate::status::driver_override_mode;
+use crate::topics::{ServiceKey, Topics};
+use crate::transport::Mode;
+
+const ENGAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Target speed (m/s, always positive) and steering tire angle (radian)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ControlTarget {
+    pub velocity: f32,
+    pub steering_tire_angle: f32,
+}
+
+/// Async counterpart of [`ManualController`](crate::ManualController) built on Zenoh's async API.
+/// It must be created and used inside a tokio runtime.
+pub struct AsyncManualController {
+    // key expressions
+    topics: Topics,
+    // Session
+    z_session: Arc<Session>,
+    // GUID
+    guid: i64,
+    // service sequence
+    sequence_number: AtomicU64,
+    // publisher
+    publisher_gate_mode: Publisher<'static>,
+    publisher_gear_command: Publisher<'static>,
+    // settings
+    settings: Settings,
+    // targets and status shared with the tasks
+    state: Arc<watch::Sender<ControlInput>>,
+    // tasks
+    status_task: JoinHandle<()>,
+    control_task: Option<JoinHandle<()>>,
+}
+
+impl AsyncManualController {
+    /// Declare the publishers and start tracking the vehicle status.
+    pub async fn new(z_session: Arc<Session>, mode: Mode, prefix: String) -> Self {
+        let topics = Topics::new(mode, &prefix);
+        let publisher_gate_mode = z_session
+            .declare_publisher(topics.gate_mode_cmd.clone())
+            .res()
+            .await
+            .unwrap();
+        let publisher_gear_command = z_session
+            .declare_publisher(topics.gear_cmd.clone())
+            .res()
+            .await
+            .unwrap();
+        let (state, _) = watch::channel(ControlInput::default());
+        let state = Arc::new(state);
+
+        let gate_mode =
+            subscribe::<tier4_control_msgs::GateMode>(&z_session, &topics.current_gate_mode).await;
+        let gear =
+            subscribe::<autoware_auto_vehicle_msgs::GearCommand>(&z_session, &topics.gear_status)
+                .await;
+        let velocity = subscribe::<autoware_auto_vehicle_msgs::VelocityReport>(
+            &z_session,
+            &topics.velocity_status,
+        )
+        .await;
+        let steering = subscribe::<autoware_auto_vehicle_msgs::SteeringReport>(
+            &z_session,
+            &topics.steering_status,
+        )
+        .await;
+        let control_mode = subscribe::<autoware_auto_vehicle_msgs::ControlModeReport>(
+            &z_session,
+            &topics.control_mode,
+        )
+        .await;
+        let status_task = tokio::spawn(track_status(
+            state.clone(),
+            gate_mode,
+            gear,
+            velocity,
+            steering,
+            control_mode,
+        ));
+
+        AsyncManualController {
+            // key expressions
+            topics,
+            // Session
+            z_session,
+            // GUID
+            guid: rand::random::<i64>(),
+            // service sequence
+            sequence_number: AtomicU64::default(),
+            // publisher
+            publisher_gate_mode,
+            publisher_gear_command,
+            // settings
+            settings: Settings::default(),
+            // targets and status shared with the tasks
+            state,
+            // tasks
+            status_task,
+            control_task: None,
+        }
+    }
+
+    pub fn topics(&self) -> &Topics {
+        &self.topics
+    }
+
+    /// Take effect on the next `start`.
+    pub fn set_message_set(&mut self, message_set: MessageSet) {
+        self.topics.message_set = message_set;
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Take effect on the next `start`.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+    }
+
+    /// Cap the speed (m/s) on top of the limits of the settings, or remove the cap with None.
+    pub fn set_speed_cap(&self, cap: Option<f32>) {
+        self.state.send_modify(|state| state.speed_cap = cap);
+    }
+
+    /// Spawn the task publishing control commands at 30 Hz.
+    /// Return false if the task is already running.
+    pub async fn start(&mut self, publish_policy: PublishPolicy) -> bool {
+        if self.control_task.is_some() {
+            log::warn!("Control task is already running\r");
+            return false;
+        }
+        let publisher_control_command = self
+            .z_session
+            .declare_publisher(self.topics.control_cmd.clone())
+            .res()
+            .await
+            .unwrap();
+        // The task switches to AUTO itself at the end of a handover ramp
+        let publisher_gate_mode = self
+            .z_session
+            .declare_publisher(self.topics.gate_mode_cmd.clone())
+            .res()
+            .await
+            .unwrap();
+        let state = self.state.clone();
+        let message_set = self.topics.message_set;
+        let settings = self.settings.clone();
+        self.control_task = Some(tokio::spawn(async move {
+            let targets_on_auto = settings.control.targets_on_auto;
+            let mut control_loop = ControlLoop::with_settings(publish_policy, settings);
+            let mut interval = tokio::time::interval(CONTROL_PERIOD);
+            let mut last_gate_mode = None;
+            loop {
+                interval.tick().await;
+                let gate_mode = state.borrow().gate_mode;
+                if last_gate_mode == Some(tier4_control_msgs::gate_mode_data::EXTERNAL)
+                    && gate_mode == tier4_control_msgs::gate_mode_data::AUTO
+                    && targets_on_auto == TargetPolicy::Reset
+                {
+                    log::info!("Back to AUTO, reset the targets\r");
+                    state.send_modify(|state| {
+                        state.target_velocity = 0.0;
+                        state.steering_tire_angle = 0.0;
+                    });
+                }
+                last_gate_mode = Some(gate_mode);
+                let input = *state.borrow();
+                let command = control_loop.step(&input);
+                match control_loop.handover() {
+                    Handover::Done if input.handover => {
+                        log::info!("Stopped, handing over to AUTO\r");
+                        state.send_modify(|state| {
+                            state.handover = false;
+                            state.target_velocity = 0.0;
+                        });
+                        let gate_mode = tier4_control_msgs::GateMode {
+                            data: tier4_control_msgs::gate_mode_data::AUTO,
+                        };
+                        let encoded = cdr::serialize::<_, _, CdrLe>(&gate_mode, Infinite).unwrap();
+                        if let Err(err) = publisher_gate_mode.put(encoded).res().await {
+                            log::error!("Failed to publish gate mode: {:?}\r", err);
+                        }
+                    }
+                    Handover::Cancelled => state.send_modify(|state| state.handover = false),
+                    _ => {}
+                }
+                if let Some(control_cmd) = command {
+                    let encoded = encode_control_command(message_set, &control_cmd);
+                    if let Err(err) = publisher_control_command.put(encoded).res().await {
+                        log::error!("Failed to publish control command: {:?}\r", err);
+                    }
+                }
+            }
+        }));
+        true
+    }
+
+    /// Stop the control task and wait for it to exit.
+    pub async fn stop(&mut self) {
+        if let Some(handle) = self.control_task.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.control_task.is_some()
+    }
+
+    async fn pub_gate_mode(&self, mode: u8) {
+        let gate_mode_data = tier4_control_msgs::GateMode { data: mode };
+        let encoded = cdr::serialize::<_, _, CdrLe>(&gate_mode_data, Infinite).unwrap();
+        self.publisher_gate_mode.put(encoded).res().await.unwrap();
+    }
+
+    /// Send the engage request and return the response of Autoware.
+    pub async fn engage(&self) -> EngageResult {
+        let result = match &self.topics.engage {
+            ServiceKey::Query(key) => self.query_engage(key).await,
+            ServiceKey::Topic { request, reply } => self.request_engage(request, reply).await,
+        }
+        .and_then(engage_result);
+        match &result {
+            Ok(status) => log::info!("Engage Received ({})\r", status),
+            Err(err) => log::error!("Engage failed: {}\r", err),
+        }
+        result
+    }
+
+    async fn query_engage(
+        &self,
+        key: &str,
+    ) -> Result<tier4_external_api_msgs::ResponseStatus, EngageError> {
+        let encoded = cdr::serialize::<_, _, CdrLe>(&true, Infinite).unwrap();
+        let replies = self
+            .z_session
+            .get(key)
+            .with_value(encoded)
+            .timeout(ENGAGE_TIMEOUT)
+            .res()
+            .await
+            .unwrap();
+        let reply = replies
+            .recv_async()
+            .await
+            .map_err(|err| EngageError::NoResponse(format!("{:?}", err)))?;
+        let sample = reply
+            .sample
+            .map_err(|err| EngageError::NoResponse(format!("{:?}", err)))?;
+        service::response::<tier4_external_api_msgs::EngageResponse>(
+            key,
+            &sample.payload.contiguous(),
+        )
+        .map(|engage| engage.status)
+        .map_err(EngageError::NoResponse)
+    }
+
+    async fn request_engage(
+        &self,
+        request_key: &str,
+        reply_key: &str,
+    ) -> Result<tier4_external_api_msgs::ResponseStatus, EngageError> {
+        let seq = self.sequence_number.fetch_add(1, Ordering::Relaxed);
+        log::info!("Sending Engage: guid={}, seq={}\r", self.guid as u64, seq);
+        let subscriber = self
+            .z_session
+            .declare_subscriber(reply_key)
+            .res()
+            .await
+            .unwrap();
+        self.z_session
+            .put(request_key, service::request_payload(self.guid, seq, &true))
+            .res()
+            .await
+            .unwrap();
+        // Skip the replies to the other clients
+        let deadline = tokio::time::Instant::now() + ENGAGE_TIMEOUT;
+        loop {
+            let sample = tokio::time::timeout_at(deadline, subscriber.recv_async())
+                .await
+                .map_err(|_| EngageError::NoResponse(format!("No reply on {}", reply_key)))?
+                .map_err(|err| EngageError::NoResponse(format!("{:?}", err)))?;
+            if let Some(engage) = service::reply_body::<tier4_external_api_msgs::EngageResponse>(
+                &sample.payload.contiguous(),
+                self.guid,
+                seq,
+            ) {
+                return Ok(engage.status);
+            }
+        }
+    }
+
+    /// With a `handover_ramp_time`, the switch to AUTO waits for the control task to ramp
+    /// down to a stop. Toggling again during the ramp cancels it and stays in EXTERNAL.
+    pub async fn toggle_gate_mode(&self) -> bool {
+        // Return whether switch to external or not
+        if self
+            .state
+            .send_if_modified(|state| std::mem::take(&mut state.handover))
+        {
+            log::info!("Handover to AUTO cancelled, staying in EXTERNAL\r");
+            return true;
+        }
+        if self.state.borrow().gate_mode == tier4_control_msgs::gate_mode_data::AUTO {
+            // Auto => External
+            if self.settings.control.bumpless_takeover {
+                // Start from the reported motion rather than the last targets
+                let (velocity, angle) =
+                    ControlLoop::takeover_targets(&self.settings, &self.state.borrow());
+                self.state.send_modify(|state| {
+                    state.target_velocity = velocity;
+                    state.steering_tire_angle = angle;
+                });
+            }
+            self.pub_gate_mode(tier4_control_msgs::gate_mode_data::EXTERNAL)
+                .await;
+            let _ = self.engage().await;
+            true
+        } else {
+            // External => Auto
+            if self.settings.control.handover_ramp_time > 0.0 && self.is_running() {
+                // The control task ramps down, then switches to AUTO
+                self.state.send_modify(|state| state.handover = true);
+            } else {
+                self.pub_gate_mode(tier4_control_msgs::gate_mode_data::AUTO)
+                    .await;
+            }
+            false
+        }
+    }
+
+    pub async fn pub_gear_command(&self, command: u8) {
+        let gear_command = autoware_auto_vehicle_msgs::GearCommand {
+            stamp: builtin_interfaces::Time { sec: 0, nanosec: 0 },
+            command,
+        };
+        let encoded = cdr::serialize::<_, _, CdrLe>(&gear_command, Infinite).unwrap();
+        self.publisher_gear_command
+            .put(encoded)
+            .res()
+            .await
+            .unwrap();
+    }
+
+    /// Whether the control task is ramping down before switching to AUTO
+    pub fn handing_over(&self) -> bool {
+        self.state.borrow().handover
+    }
+
+    /// A new target cancels a handover to AUTO.
+    pub fn update_control_command(&self, target: ControlTarget) {
+        set_target(&self.state, target);
+    }
+
+    /// Whether the vehicle reports a manual or disengaged control mode, in which case the
+    /// targets are rejected and the control task commands a stop
+    pub fn motion_blocked(&self) -> bool {
+        self.state.borrow().driver_override
+    }
+
+    /// A sink feeding the control task, handy to forward a stream of targets.
+    pub fn command_sink(&self) -> impl Sink<ControlTarget, Error = Infallible> {
+        futures::sink::unfold(
+            self.state.clone(),
+            |state, target: ControlTarget| async move {
+                set_target(&state, target);
+                Ok(state)
+            },
+        )
+    }
+
+    /// Latest targets and vehicle status seen by the control task
+    pub fn control_input(&self) -> ControlInput {
+        *self.state.borrow()
+    }
+
+    pub async fn gate_mode_stream(&self) -> impl Stream<Item = tier4_control_msgs::GateMode> {
+        subscribe(&self.z_session, &self.topics.current_gate_mode).await
+    }
+
+    pub async fn engage_stream(&self) -> impl Stream<Item = autoware_auto_vehicle_msgs::Engage> {
+        subscribe(&self.z_session, &self.topics.engage_status).await
+    }
+
+    pub async fn gear_stream(&self) -> impl Stream<Item = autoware_auto_vehicle_msgs::GearCommand> {
+        subscribe(&self.z_session, &self.topics.gear_status).await
+    }
+
+    pub async fn velocity_stream(
+        &self,
+    ) -> impl Stream<Item = autoware_auto_vehicle_msgs::VelocityReport> {
+        subscribe(&self.z_session, &self.topics.velocity_status).await
+    }
+}
+
+impl Drop for AsyncManualController {
+    fn drop(&mut self) {
+        self.status_task.abort();
+        if let Some(handle) = self.control_task.take() {
+            handle.abort();
+        }
+    }
+}
+
+/// Subscribe to `key` and deserialize every sample as `T`, skipping the malformed ones.
+pub async fn subscribe<T: DeserializeOwned>(
+    z_session: &Arc<Session>,
+    key: &str,
+) -> impl Stream<Item = T> {
+    let subscriber = z_session
+        .declare_subscriber(key.to_owned())
+        .res()
+        .await
+        .unwrap();
+    stream::unfold(subscriber, |subscriber| async move {
+        let sample = subscriber.recv_async().await.ok()?;
+        Some((sample, subscriber))
+    })
+    .filter_map(|sample| async move {
+        cdr::deserialize_from::<_, T, _>(&*sample.payload.contiguous(), cdr::size::Infinite).ok()
+    })
+}
+
+fn set_target(state: &watch::Sender<ControlInput>, target: ControlTarget) {
+    if state.borrow().driver_override {
+        log::warn!("The driver has the vehicle, ignoring the motion command\r");
+        return;
+    }
+    state.send_modify(|state| {
+        if std::mem::take(&mut state.handover) {
+            log::info!("Handover to AUTO cancelled by a new target\r");
+        }
+        state.target_velocity = target.velocity;
+        state.steering_tire_angle = target.steering_tire_angle;
+    });
+}
+
+async fn track_status(
+    state: Arc<watch::Sender<ControlInput>>,
+    gate_mode: impl Stream<Item = tier4_control_msgs::GateMode>,
+    gear: impl Stream<Item = autoware_auto_vehicle_msgs::GearCommand>,
+    velocity: impl Stream<Item = autoware_auto_vehicle_msgs::VelocityReport>,
+    steering: impl Stream<Item = autoware_auto_vehicle_msgs::SteeringReport>,
+    control_mode: impl Stream<Item = autoware_auto_vehicle_msgs::ControlModeReport>,
+) {
+    futures::pin_mut!(gate_mode, gear, velocity, steering, control_mode);
+    loop {
+        tokio::select! {
+            Some(gatemode) = gate_mode.next() => {
+                log::debug!("Subscribe gatemode.data={}\r", gatemode.data);
+                state.send_modify(|state| state.gate_mode = gatemode.data);
+            }
+            Some(gearcmd) = gear.next() => {
+                log::debug!("Subscribe GearCommand: {}\r", gearcmd.command);
+                state.send_modify(|state| state.gear = gearcmd.command);
+            }
+            Some(report) = velocity.next() => {
+                log::debug!("Subscribe VelocityReport: {}\r", report.longitudinal_velocity);
+                state.send_modify(|state| state.current_velocity = report.longitudinal_velocity);
+            }
+            Some(report) = steering.next() => {
+                log::debug!("Subscribe SteeringReport: {}\r", report.steering_tire_angle);
+                state.send_modify(|state| {
+                    state.current_steering_tire_angle = report.steering_tire_angle
+                });
+            }
+            Some(report) = control_mode.next() => {
+                log::debug!("Subscribe ControlModeReport: {}\r", report.mode);
+                state.send_modify(|state| {
+                    state.driver_override = driver_override_mode(report.mode);
+                    if state.driver_override {
+                        // Start from a stop when the vehicle is handed back
+                        state.target_velocity = 0.0;
+                    }
+                });
+            }
+            else => break,
+        }
+    }
+}
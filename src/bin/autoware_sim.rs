@@ -0,0 +1,74 @@
+use autoware_manual_control::{
+    sim::Simulator, transport, MessageSet, Mode, Settings, Topics, ZenohTransport,
+};
+use clap::Parser;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[clap(version, about)]
+/// Autoware stand-in speaking the same Zenoh keys as Autoware behind the bridge
+struct Arguments {
+    #[clap(short, long)]
+    /// The configuration file. Currently, this file must be a valid JSON5 file.
+    config: Option<String>,
+    #[clap(short, long)]
+    /// A locator on which this simulator will listen for incoming sessions.
+    /// Repeat this option to open several listeners.
+    listen: Option<Vec<String>>,
+    #[clap(short, long)]
+    /// A string added as prefix to all the simulated topics, like the scope / namespace of the bridge.
+    prefix: Option<String>,
+    #[clap(short, long, value_enum)]
+    /// Which kind of bridge to imitate: zenoh-bridge-dds or zenoh-bridge-ros2dds.
+    mode: Option<Mode>,
+    #[clap(short, long)]
+    /// The controller settings file (JSON5), for the vehicle wheelbase.
+    settings: Option<String>,
+    #[clap(short, long)]
+    /// Wheelbase of the simulated vehicle (m), instead of the one of the settings.
+    wheelbase: Option<f32>,
+    #[clap(long, value_enum)]
+    /// Message types of the control command: autoware_auto_* (auto) or autoware_control_msgs (new).
+    messages: Option<MessageSet>,
+}
+
+fn main() {
+    pretty_env_logger::init();
+
+    let args = Arguments::parse();
+    log::info!("Argument: {:?}", args);
+    let config =
+        transport::load_config(args.config.as_deref(), &args.listen.unwrap_or_default()).unwrap();
+    let mode = args.mode.unwrap_or(Mode::DDS);
+    let prefix = transport::topic_prefix(args.prefix.as_deref());
+    let z_session = transport::open_session(config).unwrap();
+
+    let settings = match args.settings {
+        Some(path) => Settings::from_file(path).unwrap(),
+        None => Settings::default(),
+    };
+
+    let topics = Topics::new(mode, &prefix).with_message_set(args.messages.unwrap_or_default());
+    let mut simulator = Simulator::with_topics(
+        Arc::new(ZenohTransport::new(z_session)),
+        topics,
+        args.wheelbase.unwrap_or(settings.vehicle.wheelbase),
+    );
+    simulator.start();
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        let state = simulator.state();
+        println!(
+            "x:{:.2}\ty:{:.2}\tyaw(deg):{:.1}\tvelocity(km/hr):{:.1}\tGear:{}\tGate Mode:{}\tEngage:{}",
+            state.x,
+            state.y,
+            state.yaw.to_degrees(),
+            state.velocity * 3.6,
+            autoware_manual_control::status::gear_name(state.gear),
+            autoware_manual_control::status::gate_mode_name(state.gate_mode),
+            state.engage
+        );
+    }
+}
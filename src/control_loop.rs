@@ -0,0 +1,613 @@
+use clap::ValueEnum;
+use std::time::{Duration, SystemTime};
+use zenoh_ros_type::{
+    autoware_auto_control_msgs, autoware_auto_vehicle_msgs, builtin_interfaces, tier4_control_msgs,
+};
+
+use crate::settings::{ControlSettings, Settings};
+
+pub const CONTROL_PERIOD: Duration = Duration::from_millis(33); // 30 Hz
+pub(crate) const ZERO_BURST_CYCLES: u32 = 10; // ~330 ms at 30 Hz
+const MAX_ACCELERATION: f32 = 1.0; // m/s^2
+
+// PI gains of the cruise control speed hold
+const CRUISE_KP: f32 = 1.0;
+const CRUISE_KI: f32 = 0.2;
+
+/// When the control loop streams `external/selected/control_cmd`
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum PublishPolicy {
+    /// Stream control commands regardless of the gate mode
+    Always,
+    /// Stream control commands only while the gate mode is EXTERNAL
+    ExternalOnly,
+}
+
+/// Everything the control loop needs to know in one cycle
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ControlInput {
+    // settings
+    pub target_velocity: f32,
+    pub steering_tire_angle: f32,
+    // status
+    pub gate_mode: u8,
+    pub gear: u8,
+    pub current_velocity: f32,
+    /// Reported steering tire angle (radian)
+    pub current_steering_tire_angle: f32,
+    /// Speed held by the cruise control, replacing `target_velocity`
+    pub cruise_target: Option<f32>,
+    /// Brake demand, from 0 (released) to 1 (full brake)
+    pub brake: f32,
+    /// Runtime cap (m/s) on top of the speed limits of the settings
+    pub speed_cap: Option<f32>,
+    /// The vehicle reports a manual or disengaged control mode: the driver has it
+    pub driver_override: bool,
+    /// The operator asked to hand over to AUTO: ramp down to a stop first
+    pub handover: bool,
+}
+
+/// Where a handover to AUTO asked for with `ControlInput::handover` stands
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Handover {
+    #[default]
+    Idle,
+    /// Ramping the last command down to a stop, still in EXTERNAL
+    Ramping,
+    /// Stopped: switch to AUTO now
+    Done,
+    /// Braking, a driver override or a gate mode change cut the ramp short
+    Cancelled,
+}
+
+/// The last command scaled down to a stop over a fixed number of cycles
+struct Ramp {
+    speed: f32,
+    deceleration: f32,
+    steering_tire_angle: f32,
+    cycles: u32,
+    remaining: u32,
+}
+
+impl Ramp {
+    fn new((speed, steering_tire_angle): (f32, f32), time: f32) -> Self {
+        let cycles = (time / CONTROL_PERIOD.as_secs_f32()).ceil().max(1.0) as u32;
+        Ramp {
+            speed,
+            deceleration: speed.abs() / (cycles as f32 * CONTROL_PERIOD.as_secs_f32()),
+            steering_tire_angle,
+            cycles,
+            remaining: cycles,
+        }
+    }
+
+    fn next(&mut self) -> Option<autoware_auto_control_msgs::AckermannControlCommand> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(new_control_command(
+            self.speed * self.remaining as f32 / self.cycles as f32,
+            -self.deceleration,
+            self.steering_tire_angle,
+        ))
+    }
+}
+
+/// Turn the operator targets into control commands, one cycle at a time
+pub struct ControlLoop {
+    publish_policy: PublishPolicy,
+    settings: Settings,
+    last_active: bool,
+    last_override: bool,
+    zero_burst: u32,
+    cruise_integral: f32,
+    /// (speed, steering tire angle) of the last command, where a handover ramp starts
+    last_command: (f32, f32),
+    ramp: Option<Ramp>,
+    handover: Handover,
+}
+
+impl ControlLoop {
+    pub fn new(publish_policy: PublishPolicy) -> Self {
+        Self::with_settings(publish_policy, Settings::default())
+    }
+
+    /// Highest speed (m/s) allowed in `gear` under `speed_cap`
+    pub fn speed_limit(settings: &ControlSettings, gear: u8, speed_cap: Option<f32>) -> f32 {
+        let limit = if gear == autoware_auto_vehicle_msgs::gear_command::REVERSE {
+            settings.max_reverse_speed
+        } else {
+            settings.max_speed
+        };
+        speed_cap.map_or(limit, |cap| limit.min(cap)).max(0.0)
+    }
+
+    /// Largest steering tire angle (radian) at `velocity`, keeping the lateral acceleration
+    /// of the bicycle model, v^2 * tan(angle) / wheelbase, below the limit
+    pub fn steering_limit(settings: &Settings, velocity: f32) -> f32 {
+        let control = &settings.control;
+        let v2 = velocity * velocity;
+        if v2 <= f32::EPSILON {
+            return control.max_steer_angle;
+        }
+        (control.max_lateral_acceleration * settings.vehicle.wheelbase / v2)
+            .atan()
+            .min(control.max_steer_angle)
+    }
+
+    pub fn with_settings(publish_policy: PublishPolicy, settings: Settings) -> Self {
+        ControlLoop {
+            publish_policy,
+            settings,
+            last_active: publish_policy == PublishPolicy::Always,
+            last_override: false,
+            zero_burst: 0,
+            cruise_integral: 0.0,
+            last_command: (0.0, 0.0),
+            ramp: None,
+            handover: Handover::Idle,
+        }
+    }
+
+    /// Targets (speed, steering) for a bumpless takeover: the reported motion, within the limits
+    pub fn takeover_targets(settings: &Settings, input: &ControlInput) -> (f32, f32) {
+        let velocity = input.current_velocity.abs().min(Self::speed_limit(
+            &settings.control,
+            input.gear,
+            input.speed_cap,
+        ));
+        let limit = Self::steering_limit(settings, velocity);
+        let steering_tire_angle = num::clamp(input.current_steering_tire_angle, -limit, limit);
+        (velocity, steering_tire_angle)
+    }
+
+    pub fn handover(&self) -> Handover {
+        self.handover
+    }
+
+    fn update_handover(&mut self, input: &ControlInput) {
+        if !input.handover {
+            self.ramp = None;
+            self.handover = Handover::Idle;
+            return;
+        }
+        match self.handover {
+            Handover::Idle => {
+                log::info!("Ramping down to a stop before handing over to AUTO\r");
+                self.ramp = Some(Ramp::new(
+                    self.last_command,
+                    self.settings.control.handover_ramp_time,
+                ));
+                self.handover = Handover::Ramping;
+            }
+            Handover::Ramping
+                if input.brake > 0.0
+                    || input.driver_override
+                    || input.gate_mode != tier4_control_msgs::gate_mode_data::EXTERNAL =>
+            {
+                log::info!("Handover to AUTO cancelled\r");
+                self.ramp = None;
+                self.handover = Handover::Cancelled;
+            }
+            _ => {}
+        }
+    }
+
+    /// Return the command to publish in this cycle, or None if nothing should be published.
+    pub fn step(
+        &mut self,
+        input: &ControlInput,
+    ) -> Option<autoware_auto_control_msgs::AckermannControlCommand> {
+        self.update_handover(input);
+        let active = self.publish_policy == PublishPolicy::Always
+            || input.gate_mode == tier4_control_msgs::gate_mode_data::EXTERNAL;
+        if active != self.last_active {
+            let bumpless = active && self.settings.control.bumpless_takeover;
+            if !bumpless {
+                // Let vehicle_cmd_gate see a clean stop around every hand-over
+                log::info!("Gate mode changed, sending zero control command\r");
+                self.zero_burst = ZERO_BURST_CYCLES;
+            }
+            self.last_active = active;
+        }
+        if self.zero_burst > 0 {
+            self.zero_burst -= 1;
+            return Some(new_control_command(0.0, 0.0, 0.0));
+        }
+        if let Some(ramp) = self.ramp.as_mut() {
+            match ramp.next() {
+                Some(command) => return Some(command),
+                None => {
+                    self.ramp = None;
+                    self.handover = Handover::Done;
+                }
+            }
+        }
+        if self.handover == Handover::Done {
+            // Hold the stop until AUTO takes over
+            return Some(new_control_command(0.0, 0.0, self.last_command.1));
+        }
+        if !active {
+            return None;
+        }
+        let command = self.command(input);
+        self.last_command = (
+            command.longitudinal.speed,
+            command.lateral.steering_tire_angle,
+        );
+        Some(command)
+    }
+
+    fn command(
+        &mut self,
+        input: &ControlInput,
+    ) -> autoware_auto_control_msgs::AckermannControlCommand {
+        if input.driver_override != self.last_override {
+            if input.driver_override {
+                log::warn!("The driver has the vehicle, blocking the motion commands\r");
+            }
+            self.last_override = input.driver_override;
+        }
+        if input.driver_override {
+            // Nothing to catch up with once the vehicle is back under control
+            return new_control_command(0.0, 0.0, 0.0);
+        }
+        if input.brake > 0.0 {
+            // Slow down whatever the target speed
+            let deceleration = input.brake.min(1.0) * self.settings.control.max_deceleration;
+            return new_control_command(
+                0.0,
+                -deceleration,
+                self.limit_steering(input.steering_tire_angle, input.current_velocity.abs()),
+            );
+        }
+        // Whatever the input source, never exceed the limits
+        let limit = Self::speed_limit(&self.settings.control, input.gear, input.speed_cap);
+        let (target_velocity, acceleration) = match input.cruise_target {
+            Some(target) => {
+                let target = target.min(limit);
+                (
+                    target,
+                    self.cruise_acceleration(target, input.current_velocity),
+                )
+            }
+            None => {
+                self.cruise_integral = 0.0;
+                let target_velocity = input.target_velocity.min(limit);
+                let acceleration = num::clamp(
+                    target_velocity - input.current_velocity.abs(),
+                    -MAX_ACCELERATION,
+                    MAX_ACCELERATION,
+                );
+                (target_velocity, acceleration)
+            }
+        };
+        // Limit for the faster of the current and the target speed
+        let steering_tire_angle = self.limit_steering(
+            input.steering_tire_angle,
+            input.current_velocity.abs().max(target_velocity),
+        );
+        log::debug!(
+            "target velocity:{}, target angle:{}\r",
+            target_velocity,
+            steering_tire_angle
+        );
+        let real_target_velocity = target_velocity
+            * (if input.gear == autoware_auto_vehicle_msgs::gear_command::DRIVE {
+                1.0
+            } else {
+                -1.0
+            });
+        new_control_command(real_target_velocity, acceleration, steering_tire_angle)
+    }
+
+    fn limit_steering(&self, steering_tire_angle: f32, velocity: f32) -> f32 {
+        let limit = Self::steering_limit(&self.settings, velocity);
+        num::clamp(steering_tire_angle, -limit, limit)
+    }
+
+    /// PI on the reported velocity, so the cruise holds its speed without steady-state error
+    fn cruise_acceleration(&mut self, target: f32, current_velocity: f32) -> f32 {
+        let error = target - current_velocity.abs();
+        let acceleration = CRUISE_KP * error + CRUISE_KI * self.cruise_integral;
+        if acceleration.abs() < MAX_ACCELERATION {
+            // No windup while saturated
+            self.cruise_integral += error * CONTROL_PERIOD.as_secs_f32();
+        }
+        num::clamp(acceleration, -MAX_ACCELERATION, MAX_ACCELERATION)
+    }
+}
+
+pub fn ros_time_now() -> builtin_interfaces::Time {
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    builtin_interfaces::Time {
+        sec: current_time.as_secs() as i32,
+        nanosec: current_time.subsec_nanos(),
+    }
+}
+
+pub fn new_control_command(
+    speed: f32,
+    acceleration: f32,
+    steering_tire_angle: f32,
+) -> autoware_auto_control_msgs::AckermannControlCommand {
+    let ros_time = ros_time_now();
+    autoware_auto_control_msgs::AckermannControlCommand {
+        stamp: ros_time.clone(),
+        lateral: autoware_auto_control_msgs::AckermannLateralCommand {
+            stamp: ros_time.clone(),
+            steering_tire_angle,
+            steering_tire_rotation_rate: 0.0,
+        },
+        longitudinal: autoware_auto_control_msgs::LongitudinalCommand {
+            stamp: ros_time,
+            speed,
+            acceleration,
+            jerk: 0.0,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zenoh_ros_type::autoware_auto_vehicle_msgs::gear_command;
+
+    fn input(gate_mode: u8, gear: u8) -> ControlInput {
+        ControlInput {
+            target_velocity: 5.0,
+            steering_tire_angle: 0.1,
+            gate_mode,
+            gear,
+            current_velocity: 0.0,
+            current_steering_tire_angle: 0.0,
+            cruise_target: None,
+            brake: 0.0,
+            speed_cap: None,
+            driver_override: false,
+            handover: false,
+        }
+    }
+
+    #[test]
+    fn speed_follows_gear_direction() {
+        let mut control_loop = ControlLoop::new(PublishPolicy::Always);
+        let drive = control_loop
+            .step(&input(
+                tier4_control_msgs::gate_mode_data::AUTO,
+                gear_command::DRIVE,
+            ))
+            .unwrap();
+        assert_eq!(drive.longitudinal.speed, 5.0);
+        assert_eq!(drive.lateral.steering_tire_angle, 0.1);
+        let reverse = control_loop
+            .step(&input(
+                tier4_control_msgs::gate_mode_data::AUTO,
+                gear_command::REVERSE,
+            ))
+            .unwrap();
+        assert_eq!(reverse.longitudinal.speed, -5.0);
+    }
+
+    #[test]
+    fn acceleration_is_clamped() {
+        let mut control_loop = ControlLoop::new(PublishPolicy::Always);
+        let mut accelerate = input(
+            tier4_control_msgs::gate_mode_data::AUTO,
+            gear_command::DRIVE,
+        );
+        let cmd = control_loop.step(&accelerate).unwrap();
+        assert_eq!(cmd.longitudinal.acceleration, 1.0);
+        accelerate.current_velocity = 4.5;
+        let cmd = control_loop.step(&accelerate).unwrap();
+        assert_eq!(cmd.longitudinal.acceleration, 0.5);
+        accelerate.current_velocity = 10.0;
+        let cmd = control_loop.step(&accelerate).unwrap();
+        assert_eq!(cmd.longitudinal.acceleration, -1.0);
+    }
+
+    #[test]
+    fn cruise_target_replaces_target_velocity() {
+        let mut control_loop = ControlLoop::new(PublishPolicy::Always);
+        let mut cruise = input(
+            tier4_control_msgs::gate_mode_data::AUTO,
+            gear_command::DRIVE,
+        );
+        cruise.cruise_target = Some(3.0);
+        cruise.current_velocity = 2.9;
+        let first = control_loop.step(&cruise).unwrap();
+        assert_eq!(first.longitudinal.speed, 3.0);
+        // The integral term pushes harder while the error stays
+        let second = control_loop.step(&cruise).unwrap();
+        assert!(second.longitudinal.acceleration > first.longitudinal.acceleration);
+        cruise.cruise_target = None;
+        assert_eq!(control_loop.step(&cruise).unwrap().longitudinal.speed, 5.0);
+    }
+
+    #[test]
+    fn brake_decelerates_in_proportion_to_demand() {
+        let mut control_loop = ControlLoop::new(PublishPolicy::Always);
+        let mut braking = input(
+            tier4_control_msgs::gate_mode_data::AUTO,
+            gear_command::DRIVE,
+        );
+        braking.current_velocity = 5.0;
+        braking.brake = 0.5;
+        let cmd = control_loop.step(&braking).unwrap();
+        assert_eq!(cmd.longitudinal.speed, 0.0);
+        assert_eq!(cmd.longitudinal.acceleration, -1.5);
+        braking.brake = 2.0;
+        let cmd = control_loop.step(&braking).unwrap();
+        assert_eq!(cmd.longitudinal.acceleration, -3.0);
+    }
+
+    #[test]
+    fn speed_is_limited_per_gear_and_cap() {
+        let mut control_loop = ControlLoop::new(PublishPolicy::Always);
+        let mut fast = input(
+            tier4_control_msgs::gate_mode_data::AUTO,
+            gear_command::DRIVE,
+        );
+        fast.target_velocity = 50.0;
+        let cmd = control_loop.step(&fast).unwrap();
+        assert_eq!(cmd.longitudinal.speed, 27.78);
+        fast.gear = gear_command::REVERSE;
+        let cmd = control_loop.step(&fast).unwrap();
+        assert_eq!(cmd.longitudinal.speed, -5.56);
+        fast.gear = gear_command::DRIVE;
+        fast.speed_cap = Some(1.0);
+        fast.cruise_target = Some(20.0);
+        let cmd = control_loop.step(&fast).unwrap();
+        assert_eq!(cmd.longitudinal.speed, 1.0);
+    }
+
+    #[test]
+    fn driver_override_blocks_motion() {
+        let mut control_loop = ControlLoop::new(PublishPolicy::Always);
+        let mut overridden = input(
+            tier4_control_msgs::gate_mode_data::AUTO,
+            gear_command::DRIVE,
+        );
+        overridden.driver_override = true;
+        let cmd = control_loop.step(&overridden).unwrap();
+        assert_eq!(cmd.longitudinal.speed, 0.0);
+        assert_eq!(cmd.lateral.steering_tire_angle, 0.0);
+        overridden.driver_override = false;
+        assert_eq!(
+            control_loop.step(&overridden).unwrap().longitudinal.speed,
+            5.0
+        );
+    }
+
+    #[test]
+    fn steering_is_limited_at_speed() {
+        let settings = Settings::default();
+        assert_eq!(ControlLoop::steering_limit(&settings, 0.0), 0.3925);
+        let fast = ControlLoop::steering_limit(&settings, 27.78);
+        assert!(fast < 0.01);
+        // 2 m/s^2 of lateral acceleration at that angle
+        let lateral = 27.78f32.powi(2) * fast.tan() / settings.vehicle.wheelbase;
+        assert!((lateral - 2.0).abs() < 1e-3);
+
+        let mut control_loop = ControlLoop::new(PublishPolicy::Always);
+        let mut steer = input(
+            tier4_control_msgs::gate_mode_data::AUTO,
+            gear_command::DRIVE,
+        );
+        steer.steering_tire_angle = -1.0;
+        let cmd = control_loop.step(&steer).unwrap();
+        assert_eq!(
+            cmd.lateral.steering_tire_angle,
+            -ControlLoop::steering_limit(&settings, 5.0)
+        );
+    }
+
+    #[test]
+    fn external_only_sends_zero_burst_on_transition() {
+        let mut settings = Settings::default();
+        settings.control.bumpless_takeover = false;
+        let mut control_loop = ControlLoop::with_settings(PublishPolicy::ExternalOnly, settings);
+        let auto = input(
+            tier4_control_msgs::gate_mode_data::AUTO,
+            gear_command::DRIVE,
+        );
+        let external = input(
+            tier4_control_msgs::gate_mode_data::EXTERNAL,
+            gear_command::DRIVE,
+        );
+        assert!(control_loop.step(&auto).is_none());
+        for _ in 0..ZERO_BURST_CYCLES {
+            let cmd = control_loop.step(&external).unwrap();
+            assert_eq!(cmd.longitudinal.speed, 0.0);
+        }
+        assert_eq!(
+            control_loop.step(&external).unwrap().longitudinal.speed,
+            5.0
+        );
+        for _ in 0..ZERO_BURST_CYCLES {
+            assert_eq!(control_loop.step(&auto).unwrap().longitudinal.speed, 0.0);
+        }
+        assert!(control_loop.step(&auto).is_none());
+    }
+
+    #[test]
+    fn takeover_starts_from_the_reported_motion() {
+        let mut control_loop = ControlLoop::new(PublishPolicy::ExternalOnly);
+        let mut auto = input(
+            tier4_control_msgs::gate_mode_data::AUTO,
+            gear_command::DRIVE,
+        );
+        auto.current_velocity = 8.0;
+        auto.current_steering_tire_angle = 0.02;
+        assert!(control_loop.step(&auto).is_none());
+        let (velocity, angle) = ControlLoop::takeover_targets(&Settings::default(), &auto);
+        assert_eq!((velocity, angle), (8.0, 0.02));
+        let mut external = auto;
+        external.gate_mode = tier4_control_msgs::gate_mode_data::EXTERNAL;
+        external.target_velocity = velocity;
+        external.steering_tire_angle = angle;
+        // No zero command in between
+        let cmd = control_loop.step(&external).unwrap();
+        assert_eq!(cmd.longitudinal.speed, 8.0);
+        auto.current_velocity = 50.0;
+        auto.current_steering_tire_angle = 0.3;
+        let (velocity, angle) = ControlLoop::takeover_targets(&Settings::default(), &auto);
+        assert_eq!(velocity, 27.78);
+        assert_eq!(
+            angle,
+            ControlLoop::steering_limit(&Settings::default(), 27.78)
+        );
+    }
+
+    #[test]
+    fn handover_ramps_down_to_a_stop() {
+        let mut settings = Settings::default();
+        settings.control.handover_ramp_time = 0.33;
+        let mut control_loop = ControlLoop::with_settings(PublishPolicy::ExternalOnly, settings);
+        let mut external = input(
+            tier4_control_msgs::gate_mode_data::EXTERNAL,
+            gear_command::DRIVE,
+        );
+        external.current_velocity = 5.0;
+        control_loop.step(&external).unwrap();
+        external.handover = true;
+        let speeds: Vec<f32> = (0..10)
+            .map(|_| control_loop.step(&external).unwrap().longitudinal.speed)
+            .collect();
+        assert_eq!(control_loop.handover(), Handover::Ramping);
+        assert!(speeds.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(speeds[0] < 5.0);
+        assert_eq!(*speeds.last().unwrap(), 0.0);
+        let stop = control_loop.step(&external).unwrap();
+        assert_eq!(control_loop.handover(), Handover::Done);
+        assert_eq!(stop.longitudinal.speed, 0.0);
+        external.handover = false;
+        control_loop.step(&external);
+        assert_eq!(control_loop.handover(), Handover::Idle);
+    }
+
+    #[test]
+    fn braking_cancels_the_handover() {
+        let mut settings = Settings::default();
+        settings.control.handover_ramp_time = 1.0;
+        let mut control_loop = ControlLoop::with_settings(PublishPolicy::ExternalOnly, settings);
+        let mut external = input(
+            tier4_control_msgs::gate_mode_data::EXTERNAL,
+            gear_command::DRIVE,
+        );
+        external.handover = true;
+        control_loop.step(&external);
+        assert_eq!(control_loop.handover(), Handover::Ramping);
+        external.brake = 0.5;
+        let cmd = control_loop.step(&external).unwrap();
+        assert_eq!(control_loop.handover(), Handover::Cancelled);
+        assert!(cmd.longitudinal.acceleration < 0.0);
+        // Stays cancelled until the request is withdrawn
+        external.brake = 0.0;
+        control_loop.step(&external);
+        assert_eq!(control_loop.handover(), Handover::Cancelled);
+    }
+}
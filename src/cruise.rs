@@ -0,0 +1,122 @@
+use core::fmt;
+
+/// Cruise control state. Speeds are in m/s, always positive.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CruiseState {
+    #[default]
+    Off,
+    /// The control loop holds `target`
+    Active { target: f32 },
+    /// Cancelled, `resume` goes back to `target`
+    Standby { target: f32 },
+}
+
+impl fmt::Display for CruiseState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CruiseState::Off => write!(f, "Off"),
+            CruiseState::Active { target } => write!(f, "{:.1} km/hr", target * 3.6),
+            CruiseState::Standby { target } => write!(f, "Standby ({:.1} km/hr)", target * 3.6),
+        }
+    }
+}
+
+/// Set/resume/cancel logic of the cruise control. The speed is held by `ControlLoop`.
+#[derive(Debug, Default)]
+pub struct CruiseControl {
+    state: CruiseState,
+    /// Gear reported when the cruise was set or resumed
+    gear: u8,
+}
+
+impl CruiseControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn state(&self) -> CruiseState {
+        self.state
+    }
+
+    /// Speed to hold, if active
+    pub fn target(&self) -> Option<f32> {
+        match self.state {
+            CruiseState::Active { target } => Some(target),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, target: f32, gear: u8) {
+        self.state = CruiseState::Active {
+            target: target.max(0.0),
+        };
+        self.gear = gear;
+    }
+
+    /// Change the target while active.
+    pub fn adjust(&mut self, delta: f32) {
+        if let CruiseState::Active { target } = self.state {
+            self.state = CruiseState::Active {
+                target: (target + delta).max(0.0),
+            };
+        }
+    }
+
+    /// Go back to the last target. Return false if there is none.
+    pub fn resume(&mut self, gear: u8) -> bool {
+        match self.state {
+            CruiseState::Standby { target } => {
+                self.set(target, gear);
+                true
+            }
+            CruiseState::Active { .. } => true,
+            CruiseState::Off => false,
+        }
+    }
+
+    /// Return whether the cruise was active.
+    pub fn cancel(&mut self) -> bool {
+        match self.state {
+            CruiseState::Active { target } => {
+                self.state = CruiseState::Standby { target };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Cancel if the reported gear is not the one the cruise was set in.
+    /// Return whether it was cancelled.
+    pub fn check_gear(&mut self, gear: u8) -> bool {
+        gear != self.gear && self.cancel()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zenoh_ros_type::autoware_auto_vehicle_msgs::gear_command;
+
+    #[test]
+    fn cancel_and_resume() {
+        let mut cruise = CruiseControl::new();
+        assert!(!cruise.resume(gear_command::DRIVE));
+        cruise.set(10.0, gear_command::DRIVE);
+        cruise.adjust(1.0);
+        assert_eq!(cruise.target(), Some(11.0));
+        assert!(cruise.cancel());
+        assert_eq!(cruise.state(), CruiseState::Standby { target: 11.0 });
+        assert_eq!(cruise.target(), None);
+        assert!(cruise.resume(gear_command::DRIVE));
+        assert_eq!(cruise.target(), Some(11.0));
+    }
+
+    #[test]
+    fn gear_change_cancels() {
+        let mut cruise = CruiseControl::new();
+        cruise.set(10.0, gear_command::DRIVE);
+        assert!(!cruise.check_gear(gear_command::DRIVE));
+        assert!(cruise.check_gear(gear_command::REVERSE));
+        assert_eq!(cruise.target(), None);
+    }
+}
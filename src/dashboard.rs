@@ -0,0 +1,53 @@
+use crate::diagnostics::{hazard_level_name, Fault};
+use crate::vehicle_model::Pose;
+
+const PREVIEW_WIDTH: usize = 31;
+const PREVIEW_HEIGHT: usize = 15;
+
+/// Draw `path` (vehicle frame, x forward, y left) top-down around the vehicle.
+/// The preview covers `range` meters ahead, behind and to each side.
+pub fn render_preview(path: &[Pose], range: f32) -> String {
+    let mut grid = vec![vec![' '; PREVIEW_WIDTH]; PREVIEW_HEIGHT];
+    let center_row = PREVIEW_HEIGHT / 2;
+    let center_col = PREVIEW_WIDTH / 2;
+    let row_scale = center_row as f32 / range;
+    // Terminal cells are about twice as tall as wide
+    let col_scale = center_col as f32 / range;
+    for pose in path {
+        let row = center_row as f32 - pose.x * row_scale;
+        let col = center_col as f32 - pose.y * col_scale;
+        if row < 0.0 || col < 0.0 {
+            continue;
+        }
+        let (row, col) = (row.round() as usize, col.round() as usize);
+        if row < PREVIEW_HEIGHT && col < PREVIEW_WIDTH {
+            grid[row][col] = '*';
+        }
+    }
+    grid[center_row][center_col] = '^';
+
+    let border = format!("+{}+", "-".repeat(PREVIEW_WIDTH));
+    let mut lines = vec![border.clone()];
+    lines.extend(
+        grid.into_iter()
+            .map(|row| format!("|{}|", row.into_iter().collect::<String>())),
+    );
+    lines.push(border);
+    lines.join("\n")
+}
+
+/// One line per fault: its hazard level, diagnostic name and message.
+pub fn render_faults(faults: &[Fault]) -> String {
+    faults
+        .iter()
+        .map(|fault| {
+            format!(
+                "[{}] {}: {}",
+                hazard_level_name(fault.level),
+                fault.name,
+                fault.message
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,173 @@
+use serde_derive::{Deserialize, Serialize};
+use zenoh_ros_type::builtin_interfaces;
+
+/// `autoware_adapi_v1_msgs/msg/MrmState` state constants
+pub mod mrm_state {
+    pub const UNKNOWN: u16 = 0;
+    pub const NORMAL: u16 = 1;
+    pub const MRM_OPERATING: u16 = 2;
+    pub const MRM_SUCCEEDED: u16 = 3;
+    pub const MRM_FAILED: u16 = 4;
+}
+
+/// `autoware_adapi_v1_msgs/msg/MrmState` behavior constants
+pub mod mrm_behavior {
+    pub const UNKNOWN: u16 = 0;
+    pub const NONE: u16 = 1;
+    pub const EMERGENCY_STOP: u16 = 2;
+    pub const COMFORTABLE_STOP: u16 = 3;
+}
+
+/// `autoware_auto_system_msgs/msg/HazardStatus` level constants
+pub mod hazard_level {
+    pub const NO_FAULT: u8 = 0;
+    pub const SAFE_FAULT: u8 = 1;
+    pub const LATENT_FAULT: u8 = 2;
+    pub const SINGLE_POINT_FAULT: u8 = 3;
+}
+
+pub fn mrm_state_name(state: u16) -> &'static str {
+    match state {
+        mrm_state::NORMAL => "Normal",
+        mrm_state::MRM_OPERATING => "Operating",
+        mrm_state::MRM_SUCCEEDED => "Succeeded",
+        mrm_state::MRM_FAILED => "Failed",
+        _ => "Unknown",
+    }
+}
+
+pub fn mrm_behavior_name(behavior: u16) -> &'static str {
+    match behavior {
+        mrm_behavior::NONE => "None",
+        mrm_behavior::EMERGENCY_STOP => "Emergency stop",
+        mrm_behavior::COMFORTABLE_STOP => "Comfortable stop",
+        _ => "Unknown",
+    }
+}
+
+pub fn hazard_level_name(level: u8) -> &'static str {
+    match level {
+        hazard_level::NO_FAULT => "No fault",
+        hazard_level::SAFE_FAULT => "Safe fault",
+        hazard_level::LATENT_FAULT => "Latent fault",
+        hazard_level::SINGLE_POINT_FAULT => "Single point fault",
+        _ => "Unknown",
+    }
+}
+
+/// `autoware_adapi_v1_msgs/msg/MrmState`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MrmState {
+    pub stamp: builtin_interfaces::Time,
+    pub state: u16,
+    pub behavior: u16,
+}
+
+/// The minimum risk maneuver Autoware runs, if any
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mrm {
+    pub state: u16,
+    pub behavior: u16,
+}
+
+/// `diagnostic_msgs/msg/KeyValue`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyValue {
+    pub key: String,
+    pub value: String,
+}
+
+/// `diagnostic_msgs/msg/DiagnosticStatus`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticStatus {
+    /// OK = 0, WARN = 1, ERROR = 2, STALE = 3
+    pub level: u8,
+    pub name: String,
+    pub message: String,
+    pub hardware_id: String,
+    pub values: Vec<KeyValue>,
+}
+
+/// `autoware_auto_system_msgs/msg/HazardStatus`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HazardStatus {
+    pub level: u8,
+    pub emergency: bool,
+    pub emergency_holding: bool,
+    pub diag_no_fault: Vec<DiagnosticStatus>,
+    pub diag_safe_fault: Vec<DiagnosticStatus>,
+    pub diag_latent_fault: Vec<DiagnosticStatus>,
+    pub diag_single_point_fault: Vec<DiagnosticStatus>,
+}
+
+/// `autoware_auto_system_msgs/msg/HazardStatusStamped`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HazardStatusStamped {
+    pub stamp: builtin_interfaces::Time,
+    pub status: HazardStatus,
+}
+
+/// A diagnostic Autoware counts as a fault
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    /// `hazard_level` of the fault
+    pub level: u8,
+    pub name: String,
+    pub message: String,
+}
+
+impl HazardStatus {
+    /// The faults, the most severe first
+    pub fn faults(&self) -> Vec<Fault> {
+        [
+            (
+                hazard_level::SINGLE_POINT_FAULT,
+                &self.diag_single_point_fault,
+            ),
+            (hazard_level::LATENT_FAULT, &self.diag_latent_fault),
+            (hazard_level::SAFE_FAULT, &self.diag_safe_fault),
+        ]
+        .into_iter()
+        .flat_map(|(level, diagnostics)| {
+            diagnostics.iter().map(move |diagnostic| Fault {
+                level,
+                name: diagnostic.name.clone(),
+                message: diagnostic.message.clone(),
+            })
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(name: &str) -> DiagnosticStatus {
+        DiagnosticStatus {
+            level: 2,
+            name: name.to_owned(),
+            message: "timeout".to_owned(),
+            hardware_id: String::new(),
+            values: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn faults_skip_healthy_diagnostics() {
+        let status = HazardStatus {
+            level: hazard_level::SINGLE_POINT_FAULT,
+            emergency: true,
+            emergency_holding: false,
+            diag_no_fault: vec![diagnostic("/autoware/vehicle")],
+            diag_safe_fault: vec![diagnostic("/autoware/map")],
+            diag_latent_fault: Vec::new(),
+            diag_single_point_fault: vec![diagnostic("/autoware/localization")],
+        };
+        let faults = status.faults();
+        assert_eq!(faults.len(), 2);
+        assert_eq!(faults[0].level, hazard_level::SINGLE_POINT_FAULT);
+        assert_eq!(faults[0].name, "/autoware/localization");
+        assert_eq!(faults[1].level, hazard_level::SAFE_FAULT);
+    }
+}
@@ -0,0 +1,133 @@
+use core::fmt;
+use zenoh_ros_type::tier4_external_api_msgs::{self, response_status};
+
+/// `tier4_external_api_msgs/msg/ResponseStatus` code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseCode {
+    Success,
+    /// The request changed nothing, e.g. already engaged
+    Ignored,
+    /// Done, with a warning in the message
+    Warn,
+    Error,
+    Unknown(u32),
+}
+
+impl From<u32> for ResponseCode {
+    fn from(code: u32) -> Self {
+        match code {
+            response_status::SUCCESS => ResponseCode::Success,
+            response_status::IGNORED => ResponseCode::Ignored,
+            response_status::WARN => ResponseCode::Warn,
+            response_status::ERROR => ResponseCode::Error,
+            code => ResponseCode::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for ResponseCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResponseCode::Success => write!(f, "Success"),
+            ResponseCode::Ignored => write!(f, "Ignored"),
+            ResponseCode::Warn => write!(f, "Warning"),
+            ResponseCode::Error => write!(f, "Error"),
+            ResponseCode::Unknown(code) => write!(f, "Unknown code {}", code),
+        }
+    }
+}
+
+/// Response of the engage service
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngageStatus {
+    pub code: ResponseCode,
+    pub message: String,
+}
+
+impl From<tier4_external_api_msgs::ResponseStatus> for EngageStatus {
+    fn from(status: tier4_external_api_msgs::ResponseStatus) -> Self {
+        EngageStatus {
+            code: status.code.into(),
+            message: status.message,
+        }
+    }
+}
+
+impl fmt::Display for EngageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "{}", self.code)
+        } else {
+            write!(f, "{}: {}", self.code, self.message)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngageError {
+    /// Autoware answered with an error or an unknown code
+    Rejected(EngageStatus),
+    /// No answer: the service is unreachable, timed out or replied garbage
+    NoResponse(String),
+}
+
+impl fmt::Display for EngageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngageError::Rejected(status) => write!(f, "Rejected ({})", status),
+            EngageError::NoResponse(reason) => write!(f, "No response ({})", reason),
+        }
+    }
+}
+
+impl std::error::Error for EngageError {}
+
+/// Success, ignored and warning responses are fine, the others are errors.
+pub type EngageResult = Result<EngageStatus, EngageError>;
+
+pub fn engage_result(status: tier4_external_api_msgs::ResponseStatus) -> EngageResult {
+    let status = EngageStatus::from(status);
+    match status.code {
+        ResponseCode::Success | ResponseCode::Ignored | ResponseCode::Warn => Ok(status),
+        ResponseCode::Error | ResponseCode::Unknown(_) => Err(EngageError::Rejected(status)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: u32, message: &str) -> tier4_external_api_msgs::ResponseStatus {
+        tier4_external_api_msgs::ResponseStatus {
+            code,
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn codes_map_to_results() {
+        let warned = engage_result(status(response_status::WARN, "steering not centered"));
+        assert_eq!(
+            warned.unwrap().to_string(),
+            "Warning: steering not centered"
+        );
+        assert_eq!(
+            engage_result(status(response_status::IGNORED, ""))
+                .unwrap()
+                .code,
+            ResponseCode::Ignored
+        );
+        let refused = engage_result(status(response_status::ERROR, "vehicle not ready"));
+        assert_eq!(
+            refused.unwrap_err().to_string(),
+            "Rejected (Error: vehicle not ready)"
+        );
+        assert!(matches!(
+            engage_result(status(9, "")),
+            Err(EngageError::Rejected(EngageStatus {
+                code: ResponseCode::Unknown(9),
+                ..
+            }))
+        ));
+    }
+}
@@ -0,0 +1,165 @@
+use clap::ValueEnum;
+use serde_derive::{Deserialize, Serialize};
+use zenoh_ros_type::{autoware_auto_control_msgs, autoware_auto_vehicle_msgs, builtin_interfaces};
+
+use crate::settings::ControlSettings;
+
+const STOP_DECELERATION: f32 = 2.0; // m/s^2, least braking of a command to stop
+
+/// Where the commands enter Autoware's external command path
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum CommandSource {
+    /// `external/selected/*`, behind the external command selector and converter
+    #[default]
+    Selected,
+    /// `external/local/*`, the local operator input of the selector
+    Local,
+    /// `external/remote/*`, the remote operator input of the selector
+    Remote,
+}
+
+impl CommandSource {
+    /// Name of the source in the `external/<name>/*` topics
+    pub fn name(self) -> &'static str {
+        match self {
+            CommandSource::Selected => "selected",
+            CommandSource::Local => "local",
+            CommandSource::Remote => "remote",
+        }
+    }
+}
+
+/// `tier4_control_msgs/msg/ExternalCommandSelectorMode` constants
+pub mod selector_mode {
+    pub const NONE: u8 = 0;
+    pub const LOCAL: u8 = 1;
+    pub const REMOTE: u8 = 2;
+}
+
+/// `tier4_control_msgs/srv/ExternalCommandSelect` request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExternalCommandSelectRequest {
+    pub mode: u8,
+}
+
+/// `tier4_control_msgs/srv/ExternalCommandSelect` response
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExternalCommandSelectResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// `tier4_external_api_msgs/msg/GearShift` constants
+pub mod gear_shift {
+    pub const NONE: u8 = 0;
+    pub const PARKING: u8 = 1;
+    pub const REVERSE: u8 = 2;
+    pub const NEUTRAL: u8 = 3;
+    pub const DRIVE: u8 = 4;
+    pub const LOW: u8 = 5;
+}
+
+/// `tier4_external_api_msgs/msg/GearShiftStamped`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GearShiftStamped {
+    pub stamp: builtin_interfaces::Time,
+    pub gear_shift: u8,
+}
+
+/// `tier4_external_api_msgs/msg/ControlCommand`: pedals and steering, as an operator would
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ControlCommand {
+    /// Steering tire angle (radian)
+    pub steering_angle: f64,
+    pub steering_angle_velocity: f64,
+    /// 0 to 1
+    pub throttle: f64,
+    /// 0 to 1
+    pub brake: f64,
+}
+
+/// `tier4_external_api_msgs/msg/ControlCommandStamped`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ControlCommandStamped {
+    pub stamp: builtin_interfaces::Time,
+    pub control: ControlCommand,
+}
+
+/// `tier4_external_api_msgs/msg/Heartbeat`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub stamp: builtin_interfaces::Time,
+}
+
+pub fn gear_shift_of(gear: u8) -> u8 {
+    match gear {
+        autoware_auto_vehicle_msgs::gear_command::DRIVE => gear_shift::DRIVE,
+        autoware_auto_vehicle_msgs::gear_command::REVERSE => gear_shift::REVERSE,
+        autoware_auto_vehicle_msgs::gear_command::PARK => gear_shift::PARKING,
+        autoware_auto_vehicle_msgs::gear_command::NEUTRAL => gear_shift::NEUTRAL,
+        autoware_auto_vehicle_msgs::gear_command::LOW => gear_shift::LOW,
+        _ => gear_shift::NONE,
+    }
+}
+
+/// Turn the acceleration of a control command into pedal positions, full throttle being
+/// `full_throttle_acceleration` and full brake `max_deceleration`.
+/// The external command converter of Autoware derives the speed from the pedals, so a command
+/// to stop (target speed 0, e.g. the zero burst or a driver override) always brakes.
+pub fn pedal_command(
+    command: &autoware_auto_control_msgs::AckermannControlCommand,
+    settings: &ControlSettings,
+) -> ControlCommandStamped {
+    let acceleration = command.longitudinal.acceleration;
+    let (throttle, brake) = if command.longitudinal.speed == 0.0 {
+        (
+            0.0,
+            -acceleration.min(-STOP_DECELERATION) / settings.max_deceleration,
+        )
+    } else if acceleration >= 0.0 {
+        (acceleration / settings.full_throttle_acceleration, 0.0)
+    } else {
+        (0.0, -acceleration / settings.max_deceleration)
+    };
+    ControlCommandStamped {
+        stamp: command.stamp.clone(),
+        control: ControlCommand {
+            steering_angle: command.lateral.steering_tire_angle as f64,
+            steering_angle_velocity: command.lateral.steering_tire_rotation_rate as f64,
+            throttle: throttle.clamp(0.0, 1.0) as f64,
+            brake: brake.clamp(0.0, 1.0) as f64,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_loop::new_control_command;
+
+    #[test]
+    fn acceleration_maps_to_pedals() {
+        let settings = ControlSettings::default();
+        let accelerate = pedal_command(&new_control_command(5.0, 1.0, 0.1), &settings);
+        assert_eq!(accelerate.control.throttle, 0.5);
+        assert_eq!(accelerate.control.brake, 0.0);
+        assert_eq!(accelerate.control.steering_angle, 0.1f32 as f64);
+        let brake = pedal_command(&new_control_command(0.0, -6.0, 0.0), &settings);
+        assert_eq!(brake.control.throttle, 0.0);
+        assert_eq!(brake.control.brake, 1.0);
+    }
+
+    #[test]
+    fn stop_commands_brake() {
+        let settings = ControlSettings::default();
+        let stop = pedal_command(&new_control_command(0.0, 0.0, 0.0), &settings);
+        assert_eq!(stop.control.throttle, 0.0);
+        assert_eq!(
+            stop.control.brake,
+            (STOP_DECELERATION / settings.max_deceleration) as f64
+        );
+        // Harder braking is kept
+        let brake = pedal_command(&new_control_command(0.0, -6.0, 0.0), &settings);
+        assert_eq!(brake.control.brake, 1.0);
+    }
+}
@@ -0,0 +1,69 @@
+//! # autoware_manual_control
+//!
+//! Control the vehicle in Autoware with native Zenoh API.
+//! The program should run with zenoh-bridge-dds / zenoh-bridge-ros2dds.
+//!
+//! * [`ManualController`]: publish gate mode, gear and control commands, and track the vehicle status
+//! * [`AsyncManualController`]: the same on top of tokio and Zenoh's async API
+//! * [`sim`]: an Autoware stand-in simulating a kinematic bicycle vehicle, see the `autoware_sim` binary
+//! * [`ad_api`]: message types and mode selection for the Autoware AD API
+//! * [`cruise`]: cruise control set/resume/cancel logic
+//! * [`diagnostics`]: MRM state and hazard status, to tell why Autoware refuses to engage or brakes
+//! * [`engage`]: typed responses of the engage service
+//! * [`external`]: local/remote command sources of Autoware's external command selector
+//! * [`localization`]: initial pose presets and messages of the localization API
+//! * [`messages`]: the `autoware_control_msgs` command layout of recent Autoware releases
+//! * [`routing`]: goal presets and messages of the routing API
+//! * [`status`]: structured vehicle status and change events
+//! * [`topics`]: Zenoh key expressions of the Autoware topics and services
+//! * [`transport`]: Zenoh session setup and the [`Transport`] abstraction used by [`ManualController`]
+//! * [`settings`]: controller settings loaded from a JSON5 file
+//! * [`vehicle_model`]: kinematic bicycle model for dead-reckoning and path prediction
+//! * [`dashboard`]: text rendering for the terminal
+//! * [`recorder`]: record the session to an [`mcap`] file through a [`RecordingTransport`]
+//! * [`replay`]: drive the vehicle again with the commands of a recording
+//! * [`script`]: run maneuvers described in a YAML/JSON file
+//! * [`mock`]: an in-process [`Transport`] for testing without a Zenoh network
+
+pub mod ad_api;
+pub mod async_control;
+pub mod control_loop;
+pub mod cruise;
+pub mod dashboard;
+pub mod diagnostics;
+pub mod engage;
+pub mod external;
+pub mod localization;
+pub mod manual_control;
+pub mod mcap;
+pub mod messages;
+pub mod mock;
+pub mod recorder;
+pub mod replay;
+pub mod routing;
+pub mod script;
+mod service;
+pub mod settings;
+pub mod sim;
+pub mod status;
+pub mod topics;
+pub mod transport;
+pub mod vehicle_model;
+
+pub use ad_api::ControlApi;
+pub use async_control::{AsyncManualController, ControlTarget};
+pub use control_loop::PublishPolicy;
+pub use cruise::CruiseState;
+pub use engage::{EngageError, EngageResult, EngageStatus, ResponseCode};
+pub use external::CommandSource;
+pub use manual_control::ManualController;
+pub use messages::MessageSet;
+pub use mock::MockTransport;
+pub use recorder::{Recorder, RecordingTransport};
+pub use replay::{Recording, ReplayOptions, ReplayState, Replayer};
+pub use script::Script;
+pub use settings::{MapPose, Settings, TargetPolicy};
+pub use status::{ControllerStatus, LinkHealth, StatusEvent};
+pub use topics::{ServiceKey, Topics};
+pub use transport::{Mode, Transport, ZenohTransport};
+pub use vehicle_model::{Pose, VehicleModel};
@@ -0,0 +1,93 @@
+use serde_derive::{Deserialize, Serialize};
+use zenoh_ros_type::{builtin_interfaces, geometry_msgs, std_msgs};
+
+use crate::routing::ros_pose;
+use crate::settings::MapPose;
+
+/// Covariance of the "2D Pose Estimate" of RViz: 0.25 m^2 on x and y, 0.0685 rad^2 on yaw
+const INITIAL_POSE_COVARIANCE: [[f64; 6]; 6] = [
+    [0.25, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.25, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.068_538_919_091_224_67],
+];
+
+/// `autoware_adapi_v1_msgs/msg/LocalizationInitializationState` constants
+pub mod initialization_state {
+    pub const UNKNOWN: u16 = 0;
+    pub const UNINITIALIZED: u16 = 1;
+    pub const INITIALIZING: u16 = 2;
+    pub const INITIALIZED: u16 = 3;
+}
+
+pub fn initialization_state_name(state: u16) -> &'static str {
+    match state {
+        initialization_state::UNINITIALIZED => "Uninitialized",
+        initialization_state::INITIALIZING => "Initializing",
+        initialization_state::INITIALIZED => "Initialized",
+        _ => "Unknown",
+    }
+}
+
+/// `autoware_adapi_v1_msgs/msg/LocalizationInitializationState`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LocalizationInitializationState {
+    pub stamp: builtin_interfaces::Time,
+    pub state: u16,
+}
+
+/// `geometry_msgs/msg/PoseWithCovariance`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PoseWithCovariance {
+    pub pose: geometry_msgs::Pose,
+    /// Row-major 6x6 covariance of x, y, z, roll, pitch, yaw
+    pub covariance: [[f64; 6]; 6],
+}
+
+/// `geometry_msgs/msg/PoseWithCovarianceStamped`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PoseWithCovarianceStamped {
+    pub header: std_msgs::Header,
+    pub pose: PoseWithCovariance,
+}
+
+/// `autoware_adapi_v1_msgs/srv/InitializeLocalization` request
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InitializeLocalizationRequest {
+    /// At most one pose; empty lets Autoware use the GNSS pose
+    pub pose: Vec<PoseWithCovarianceStamped>,
+}
+
+/// The pose in the map frame, with the covariance RViz gives to an initial pose estimate
+pub fn initial_pose(pose: &MapPose, header: std_msgs::Header) -> PoseWithCovarianceStamped {
+    PoseWithCovarianceStamped {
+        header,
+        pose: PoseWithCovariance {
+            pose: ros_pose(pose),
+            covariance: INITIAL_POSE_COVARIANCE,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdr::{CdrLe, Infinite};
+
+    #[test]
+    fn covariance_is_a_fixed_array() {
+        let pose = initial_pose(
+            &MapPose::default(),
+            std_msgs::Header {
+                stamp: builtin_interfaces::Time { sec: 0, nanosec: 0 },
+                frame_id: String::new(),
+            },
+        );
+        let with = cdr::serialize::<_, _, CdrLe>(&pose, Infinite).unwrap();
+        let without = cdr::serialize::<_, _, CdrLe>(&(&pose.header, &pose.pose.pose), Infinite);
+        // float64[36], no length prefix
+        assert_eq!(with.len() - without.unwrap().len(), 36 * 8);
+    }
+}
@@ -1,19 +1,24 @@
-mod manual_control;
-
-use clap::{Parser, ValueEnum};
-use core::fmt;
+use autoware_manual_control::{
+    dashboard, replay, routing, transport, CommandSource, ControlApi, CruiseState,
+    ManualController, MessageSet, Mode, PublishPolicy, Recorder, Recording, RecordingTransport,
+    ReplayOptions, Replayer, Script, Settings, Topics, Transport, ZenohTransport,
+};
+use clap::Parser;
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use std::f32::consts;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 use zenoh::prelude::sync::*;
 use zenoh_ros_type::autoware_auto_vehicle_msgs;
 
-use manual_control::ManualController;
-
-const MAX_STEER_ANGLE: f32 = 0.3925; // 22.5 * (PI / 180)
 const STEP_STEER_ANGLE: f32 = 0.0174; // 1 * (PI / 180)
-const MAX_SPEED: f32 = 27.78; // 100 km/hr = 27.78 m/s
 const STEP_SPEED: f32 = 1.389; // 5 km/hr = 1.389 m/s
+const STEP_BRAKE: f32 = 0.25; // of the max deceleration
+const PREVIEW_HORIZON: f32 = 3.0; // s
+const PREVIEW_RANGE: f32 = 20.0; // m
 
 fn print_help() {
     println!("------------------------------------");
@@ -23,10 +28,25 @@ fn print_help() {
     println!("|   c: Gear Type => Reverse        |");
     println!("|   v: Gear Type => Park           |");
     println!("|   s: View current mode           |");
+    println!("|   p: Pause/Resume control cmd    |");
+    println!("|   m: Show pose & predicted path  |");
+    println!("| Routing:                         |");
+    println!("|   g: Set goal (preset or x y yaw)|");
+    println!("|   h: Clear route                 |");
+    println!("| Localization:                    |");
+    println!("|   e: Set initial pose            |");
+    println!("| Cruise Control:                  |");
+    println!("|   r: Set at current speed/Resume |");
+    println!("|   t: Cancel                      |");
+    println!("|   u/o: Adjust cruise speed       |");
     println!("| Speed:                           |");
     println!("|   u: Increase speed              |");
     println!("|   i: Set speed to 0              |");
     println!("|   o: Decrease speed              |");
+    println!("|   y: Toggle yard mode            |");
+    println!("| Brake:                           |");
+    println!("|   b: Brake harder                |");
+    println!("|   n: Release brake               |");
     println!("| Steering Angle                   |");
     println!("|   j: Left turn                   |");
     println!("|   k: Set angle to 0              |");
@@ -34,6 +54,63 @@ fn print_help() {
     println!("------------------------------------");
 }
 
+fn print_script_help() {
+    println!("------------------------------------");
+    println!("| Script:                          |");
+    println!("|   q: Abort and stop the vehicle  |");
+    println!("------------------------------------");
+}
+
+fn print_replay_help() {
+    println!("------------------------------------");
+    println!("| Replay:                          |");
+    println!("|   space: Pause/Resume            |");
+    println!("|   q: Abort and stop the vehicle  |");
+    println!("------------------------------------");
+}
+
+fn print_faults(manual_controller: &ManualController) {
+    for line in dashboard::render_faults(&manual_controller.faults()).lines() {
+        println!("  {}\r", line);
+    }
+}
+
+/// Read a line in raw mode, echoing it. `None` if cancelled with Esc or Ctrl-C.
+fn read_line(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    io::stdout().flush().ok();
+    let mut line = String::new();
+    loop {
+        let Ok(Event::Key(key)) = crossterm::event::read() else {
+            continue;
+        };
+        match key.code {
+            KeyCode::Enter => break,
+            KeyCode::Esc => {
+                println!("\r");
+                return None;
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                println!("\r");
+                return None;
+            }
+            KeyCode::Backspace => {
+                if line.pop().is_some() {
+                    print!("\u{8} \u{8}");
+                }
+            }
+            KeyCode::Char(c) => {
+                line.push(c);
+                print!("{}", c);
+            }
+            _ => continue,
+        }
+        io::stdout().flush().ok();
+    }
+    println!("\r");
+    Some(line)
+}
+
 #[derive(Parser, Debug)]
 #[clap(version, about)]
 /// Autoware keyboard controller with Zenoh
@@ -52,65 +129,255 @@ struct Arguments {
     #[clap(short, long, value_enum)]
     /// Select which kind of bridge you're using: zenoh-bridge-dds or zenoh-bridge-ros2dds.
     mode: Option<Mode>,
+    #[clap(long, value_enum)]
+    /// When to stream control commands: always, or only while the gate mode is EXTERNAL.
+    publish_policy: Option<PublishPolicy>,
+    #[clap(long, value_enum)]
+    /// How to hand the vehicle over: the legacy gate mode & engage, or the AD API operation mode.
+    api: Option<ControlApi>,
+    #[clap(long, value_enum)]
+    /// Message types of the control command: autoware_auto_* (auto) or autoware_control_msgs (new).
+    messages: Option<MessageSet>,
+    #[clap(long, value_enum)]
+    /// Where to send the commands: external/selected, or the local/remote inputs of the external command selector.
+    command_source: Option<CommandSource>,
+    #[clap(short, long)]
+    /// The controller settings file (JSON5), e.g. the vehicle wheelbase.
+    settings: Option<String>,
+    #[clap(short, long)]
+    /// Record the published commands and the received status to this MCAP file.
+    record: Option<String>,
+    #[clap(long)]
+    /// Replay the commands of a recorded MCAP file instead of reading the keyboard.
+    replay: Option<String>,
+    #[clap(long, default_value_t = 1.0, value_parser = parse_replay_speed)]
+    /// Playback speed of --replay, 1.0 keeps the original timing.
+    replay_speed: f32,
+    #[clap(long)]
+    /// Run the maneuver described in a YAML/JSON file instead of reading the keyboard.
+    script: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, ValueEnum)]
-enum Mode {
-    /// Using zenoh-bridge-dds
-    DDS,
-    /// Using zenoh-bridge-ros2dds
-    ROS2,
+struct Options {
+    mode: Mode,
+    prefix: String,
+    publish_policy: PublishPolicy,
+    api: ControlApi,
+    messages: MessageSet,
+    command_source: CommandSource,
+    settings: Settings,
+    record: Option<String>,
+    replay: Option<String>,
+    replay_speed: f32,
+    script: Option<String>,
 }
 
-impl fmt::Display for Mode {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Mode::DDS => write!(f, "dds"),
-            Mode::ROS2 => write!(f, "ros2"),
-        }
-    }
+fn parse_replay_speed(arg: &str) -> Result<f32, String> {
+    let speed = arg.parse::<f32>().map_err(|err| err.to_string())?;
+    replay::check_speed(speed)
 }
 
-fn parse_args() -> (Config, Mode, String) {
+fn parse_args() -> (Config, Options) {
     let args = Arguments::parse();
     log::info!("Argument: {:?}", args);
-    let mut config = match args.config {
-        Some(conf_file) => Config::from_file(conf_file).unwrap(),
-        None => Config::default(),
-    };
-    if let Some(endpoints) = args.listen {
-        config
-            .listen
-            .endpoints
-            .extend(endpoints.iter().map(|p| p.parse().unwrap()))
-    }
+    let config =
+        transport::load_config(args.config.as_deref(), &args.listen.unwrap_or_default()).unwrap();
     let mode = match args.mode {
         Some(m) => m,
         None => Mode::DDS,
     };
-    let prefix = match args.prefix {
-        Some(s) => s.to_string() + "/",
-        None => String::from(""),
+    let prefix = transport::topic_prefix(args.prefix.as_deref());
+    let publish_policy = match args.publish_policy {
+        Some(p) => p,
+        None => PublishPolicy::Always,
+    };
+    let settings = match args.settings {
+        Some(path) => Settings::from_file(path).unwrap(),
+        None => Settings::default(),
     };
     log::info!(
         "autoware_manual_control_rs run in {} mode with prefix {}",
         mode,
         prefix
     );
-    (config, mode, prefix)
+    let options = Options {
+        mode,
+        prefix,
+        publish_policy,
+        api: args.api.unwrap_or_default(),
+        messages: args.messages.unwrap_or_default(),
+        command_source: args.command_source.unwrap_or_default(),
+        settings,
+        record: args.record,
+        replay: args.replay,
+        replay_speed: args.replay_speed,
+        script: args.script,
+    };
+    (config, options)
+}
+
+fn run_script(manual_controller: &ManualController, path: &str) {
+    let script = Script::from_file(path).unwrap();
+    println!("Run {} steps from {}", script.steps.len(), path);
+    print_script_help();
+    let abort = AtomicBool::new(false);
+    let done = AtomicBool::new(false);
+    crossterm::terminal::enable_raw_mode().unwrap();
+    let result = thread::scope(|s| {
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                if !crossterm::event::poll(Duration::from_millis(100)).unwrap() {
+                    continue;
+                }
+                match crossterm::event::read() {
+                    Ok(Event::Key(KeyEvent {
+                        code: KeyCode::Char('q'),
+                        modifiers: _,
+                        kind: _,
+                        state: _,
+                    }))
+                    | Ok(Event::Key(KeyEvent {
+                        code: KeyCode::Char('c'),
+                        modifiers: KeyModifiers::CONTROL,
+                        kind: _,
+                        state: _,
+                    })) => {
+                        abort.store(true, Ordering::Relaxed);
+                    }
+                    _ => {}
+                }
+            }
+        });
+        let result = script.run(manual_controller, &abort);
+        done.store(true, Ordering::Relaxed);
+        result
+    });
+    crossterm::terminal::disable_raw_mode().unwrap();
+    match result {
+        Ok(()) => println!("Script finished"),
+        Err(reason) => println!("Script aborted: {}", reason),
+    }
+}
+
+fn run_replay(
+    manual_controller: &ManualController,
+    transport: Arc<dyn Transport>,
+    path: &str,
+    speed: f32,
+) {
+    let recording = Recording::load(path).unwrap();
+    println!(
+        "Replay {} commands ({:.1} s) from {} at x{}",
+        recording.commands.len(),
+        recording.duration().as_secs_f32(),
+        path,
+        speed
+    );
+    print_replay_help();
+    // The replayed control commands replace the ones from the keyboard
+    manual_controller.pause();
+    let options = ReplayOptions {
+        speed,
+        settings: manual_controller.settings().clone(),
+        speed_cap: manual_controller.speed_cap(),
+        ..Default::default()
+    };
+    let mut replayer = match Replayer::start(
+        recording,
+        transport,
+        manual_controller.topics().clone(),
+        manual_controller.status_tracker(),
+        options,
+    ) {
+        Ok(replayer) => replayer,
+        Err(err) => {
+            println!("Replay failed: {}", err);
+            return;
+        }
+    };
+    crossterm::terminal::enable_raw_mode().unwrap();
+    while !replayer.is_done() {
+        if !crossterm::event::poll(Duration::from_millis(100)).unwrap() {
+            continue;
+        }
+        match crossterm::event::read() {
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char(' '),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                if replayer.is_paused() {
+                    replayer.resume();
+                    println!("Resume replay\r");
+                } else {
+                    replayer.pause();
+                    println!("Pause replay\r");
+                }
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('q'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            }))
+            | Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: _,
+                state: _,
+            })) => {
+                replayer.abort();
+            }
+            _ => {}
+        }
+    }
+    crossterm::terminal::disable_raw_mode().unwrap();
+    let state = replayer.wait();
+    match replayer.skipped() {
+        0 => println!("Replay {:?}", state),
+        skipped => println!("Replay {:?} ({} recorded samples skipped)", state, skipped),
+    }
 }
 
 fn main() {
     pretty_env_logger::init();
 
-    let mut velocity = 0.0; // m/s
-    let mut angle = 0.0; // radian
-
-    let (config, mode, prefix) = parse_args();
-    let z_session = Arc::new(zenoh::open(config).res().unwrap());
+    let (config, options) = parse_args();
+    let z_session = transport::open_session(config).unwrap();
+    let mut transport: Arc<dyn Transport> = Arc::new(ZenohTransport::new(z_session));
+    let recorder = options.record.map(|path| {
+        let topics = Topics::new(options.mode, &options.prefix)
+            .with_message_set(options.messages)
+            .with_command_source(options.command_source);
+        let recorder = Arc::new(Recorder::create(&path, &topics).unwrap());
+        log::info!("Recording to {}", path);
+        recorder
+    });
+    if let Some(recorder) = &recorder {
+        transport = Arc::new(RecordingTransport::new(transport, recorder.clone()));
+    }
     let mut manual_controller =
-        ManualController::new(z_session.clone(), mode == Mode::ROS2, prefix);
-    manual_controller.init(z_session.clone());
+        ManualController::with_transport(transport.clone(), options.mode, options.prefix);
+    manual_controller.set_publish_policy(options.publish_policy);
+    manual_controller.set_control_api(options.api);
+    manual_controller.set_message_set(options.messages);
+    manual_controller.set_command_source(options.command_source);
+    manual_controller.set_settings(options.settings);
+    manual_controller.on_status_change(|event| log::info!("{}\r", event));
+    manual_controller.init();
+    if options.replay.is_some() || options.script.is_some() {
+        if let Some(path) = options.replay {
+            run_replay(&manual_controller, transport, &path, options.replay_speed);
+        } else if let Some(path) = options.script {
+            run_script(&manual_controller, &path);
+        }
+        manual_controller.stop();
+        if let Some(recorder) = recorder {
+            recorder.finish().unwrap();
+        }
+        return;
+    }
     print_help();
     crossterm::terminal::enable_raw_mode().unwrap();
     loop {
@@ -129,12 +396,38 @@ fn main() {
                 kind: _,
                 state: _,
             })) => {
-                let new_mode = if manual_controller.toggle_gate_mode() {
-                    "EXTERNAL"
-                } else {
-                    "AUTO"
+                if manual_controller.handing_over() {
+                    manual_controller.toggle_gate_mode();
+                    println!("Handover cancelled, staying in EXTERNAL\r");
+                    continue;
+                }
+                let external = manual_controller.toggle_gate_mode();
+                let new_mode = match (manual_controller.control_api(), external) {
+                    (ControlApi::Legacy, true) => "EXTERNAL",
+                    (ControlApi::Legacy, false) => "AUTO",
+                    (ControlApi::AdApi, true) => "LOCAL",
+                    (ControlApi::AdApi, false) => "AUTONOMOUS",
                 };
                 println!("Toggle to {}\r", new_mode);
+                if manual_controller.handing_over() {
+                    println!("Ramping down to a stop first, toggle again or drive to cancel\r");
+                }
+                if external && manual_controller.control_api() == ControlApi::Legacy {
+                    match manual_controller.last_engage() {
+                        Some(Ok(status)) => println!("Engage: {}\r", status),
+                        Some(Err(err)) => println!("Engage: {}\r", err),
+                        None => {}
+                    }
+                }
+                if !external {
+                    let (velocity, angle) = manual_controller.pending_targets();
+                    println!(
+                        "Pending targets: {:.1} km/hr, {:.1} deg\r",
+                        velocity * 3.6,
+                        angle.to_degrees()
+                    );
+                }
+                print_faults(&manual_controller);
             }
             Ok(Event::Key(KeyEvent {
                 code: KeyCode::Char('x'),
@@ -171,6 +464,143 @@ fn main() {
                 state: _,
             })) => {
                 println!("{}\r", manual_controller.get_status());
+                print_faults(&manual_controller);
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                if manual_controller.is_paused() {
+                    manual_controller.resume();
+                    println!("Resume publishing control command\r");
+                } else {
+                    manual_controller.pause();
+                    println!("Pause publishing control command\r");
+                }
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('m'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                let pose = manual_controller.status().pose;
+                println!(
+                    "x(m):{:.2}\ty(m):{:.2}\tyaw(deg):{:.1}\r",
+                    pose.x,
+                    pose.y,
+                    pose.yaw * 180.0 / consts::PI
+                );
+                let path = manual_controller.predicted_path(PREVIEW_HORIZON);
+                for line in dashboard::render_preview(&path, PREVIEW_RANGE).lines() {
+                    println!("{}\r", line);
+                }
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('b'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                manual_controller.set_brake(manual_controller.brake() + STEP_BRAKE);
+                println!("brake:{}%\r", manual_controller.brake() * 100.0);
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('n'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                manual_controller.set_brake(0.0);
+                println!("brake:{}%\r", manual_controller.brake() * 100.0);
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                if let CruiseState::Standby { .. } = manual_controller.cruise() {
+                    manual_controller.resume_cruise();
+                } else {
+                    manual_controller.set_cruise(None);
+                }
+                println!("Cruise control: {}\r", manual_controller.cruise());
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('g'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                let Some(input) = read_line("Goal (preset or x y [yaw]): ") else {
+                    continue;
+                };
+                let goals = &manual_controller.settings().routing.goals;
+                match routing::parse_pose(&input, goals) {
+                    Ok(goal) => match manual_controller.set_goal(&goal) {
+                        Ok(()) => println!(
+                            "Goal set to x(m):{:.2}\ty(m):{:.2}\tyaw(deg):{:.1}\r",
+                            goal.x, goal.y, goal.yaw
+                        ),
+                        Err(e) => println!("Failed to set the goal: {}\r", e),
+                    },
+                    Err(e) => println!("{}\r", e),
+                }
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('e'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                let Some(input) = read_line("Initial pose (preset or x y [yaw]): ") else {
+                    continue;
+                };
+                let poses = &manual_controller.settings().localization.initial_poses;
+                match routing::parse_pose(&input, poses) {
+                    Ok(pose) => match manual_controller.initialize_localization(&pose) {
+                        Ok(()) => println!(
+                            "Initial pose set to x(m):{:.2}\ty(m):{:.2}\tyaw(deg):{:.1}\r",
+                            pose.x, pose.y, pose.yaw
+                        ),
+                        Err(e) => println!("Failed to initialize the localization: {}\r", e),
+                    },
+                    Err(e) => println!("{}\r", e),
+                }
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('h'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => match manual_controller.clear_route() {
+                Ok(()) => println!("Route cleared\r"),
+                Err(e) => println!("Failed to clear the route: {}\r", e),
+            },
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('y'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                let yard = manual_controller.toggle_yard_mode();
+                println!(
+                    "Yard mode {}, speed limit(km/hr):{:.1}\r",
+                    if yard { "on" } else { "off" },
+                    manual_controller.speed_limit() * 3.6
+                );
+            }
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('t'),
+                modifiers: _,
+                kind: _,
+                state: _,
+            })) => {
+                manual_controller.cancel_cruise();
+                println!("Cruise control: {}\r", manual_controller.cruise());
             }
             Ok(Event::Key(KeyEvent {
                 code: c,
@@ -178,34 +608,57 @@ fn main() {
                 kind: _,
                 state: _,
             })) => {
+                let motion = matches!(c, KeyCode::Char('u' | 'i' | 'o' | 'j' | 'k' | 'l'));
+                if motion && manual_controller.motion_blocked() {
+                    println!("The driver has the vehicle, motion commands are blocked\r");
+                    continue;
+                }
+                // The cruise control or a takeover may have changed the targets
+                let mut velocity = manual_controller.target_velocity(); // m/s
+                let mut angle = manual_controller.target_steering_tire_angle(); // radian
+                let max_speed = manual_controller.speed_limit();
+                let max_angle = manual_controller.settings().control.max_steer_angle;
+                let cruising = matches!(manual_controller.cruise(), CruiseState::Active { .. });
                 match c {
+                    KeyCode::Char('u') if cruising => {
+                        manual_controller.adjust_cruise(STEP_SPEED);
+                        velocity = manual_controller.target_velocity();
+                    }
+                    KeyCode::Char('o') if cruising => {
+                        manual_controller.adjust_cruise(-STEP_SPEED);
+                        velocity = manual_controller.target_velocity();
+                    }
                     KeyCode::Char('u') => {
-                        velocity = num::clamp(velocity + STEP_SPEED, 0.0, MAX_SPEED)
+                        velocity = num::clamp(velocity + STEP_SPEED, 0.0, max_speed)
                     }
                     KeyCode::Char('i') => velocity = 0.0,
                     KeyCode::Char('o') => {
-                        velocity = num::clamp(velocity - STEP_SPEED, 0.0, MAX_SPEED)
+                        velocity = num::clamp(velocity - STEP_SPEED, 0.0, max_speed)
                     }
                     KeyCode::Char('j') => {
-                        angle =
-                            num::clamp(angle + STEP_STEER_ANGLE, -MAX_STEER_ANGLE, MAX_STEER_ANGLE)
+                        angle = num::clamp(angle + STEP_STEER_ANGLE, -max_angle, max_angle)
                     }
                     KeyCode::Char('k') => angle = 0.0,
                     KeyCode::Char('l') => {
-                        angle =
-                            num::clamp(angle - STEP_STEER_ANGLE, -MAX_STEER_ANGLE, MAX_STEER_ANGLE)
+                        angle = num::clamp(angle - STEP_STEER_ANGLE, -max_angle, max_angle)
                     }
                     _ => {}
                 }
                 manual_controller.update_control_command(velocity, angle);
                 println!(
-                    "angle(deg):{}\tvelocity(km/hr):{}\r",
+                    "angle(deg):{}\tlimit(deg):{:.1}\tvelocity(km/hr):{}\tcruise:{}\r",
                     (angle * 180.0 / consts::PI),
-                    (velocity * 3600.0 / 1000.0)
+                    manual_controller.steering_limit().to_degrees(),
+                    (velocity * 3600.0 / 1000.0),
+                    manual_controller.cruise()
                 );
             }
             _ => {}
         }
     }
     crossterm::terminal::disable_raw_mode().unwrap();
+    manual_controller.stop();
+    if let Some(recorder) = recorder {
+        recorder.finish().unwrap();
+    }
 }
@@ -1,370 +1,1027 @@
 use atomic_float::AtomicF32;
-use cdr::{CdrLe, Infinite};
-use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
-use std::sync::Arc;
-use std::thread;
-use std::time::{Duration, SystemTime};
-use zenoh::prelude::sync::*;
-use zenoh::publication::Publisher;
-use zenoh::subscriber::Subscriber;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use zenoh::Session;
 use zenoh_ros_type::{
-    autoware_auto_control_msgs, autoware_auto_vehicle_msgs, builtin_interfaces, service,
-    tier4_control_msgs, tier4_external_api_msgs,
+    autoware_auto_vehicle_msgs, builtin_interfaces, std_msgs, tier4_control_msgs,
+    tier4_external_api_msgs,
 };
 
-pub struct ManualController<'a> {
-    // mode
-    ros2: bool,
-    // prefix
-    prefix: String,
-    // Session
-    z_session: Arc<Session>,
+use crate::ad_api::{self, operation_mode, ControlApi};
+use crate::control_loop::{
+    ros_time_now, ControlInput, ControlLoop, Handover, PublishPolicy, CONTROL_PERIOD,
+};
+use crate::cruise::{CruiseControl, CruiseState};
+use crate::diagnostics::{self, Fault, Mrm};
+use crate::engage::{engage_result, EngageError, EngageResult};
+use crate::external::{self, selector_mode, CommandSource};
+use crate::localization;
+use crate::messages::{publish_control_command, MessageSet};
+use crate::routing::{self, route_state};
+use crate::settings::{MapPose, Settings, TargetPolicy};
+use crate::status::{control_mode_name, ControllerStatus, StatusEvent, StatusTracker};
+use crate::topics::{ServiceKey, Topics};
+use crate::transport::{publish, subscribe, Mode, Subscription, Transport, ZenohTransport};
+use crate::vehicle_model::{Pose, VehicleModel};
+
+const ENGAGE_TIMEOUT: Duration = Duration::from_secs(5);
+const SERVICE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct ManualController {
+    // key expressions
+    topics: Topics,
+    // Transport
+    transport: Arc<dyn Transport>,
     // GUID
     guid: i64,
     // service sequence
     sequence_number: Arc<AtomicU64>,
-    // service
-    key_client_engage: String,
-    // publisher
-    publisher_gate_mode: Publisher<'a>,
-    publisher_gear_command: Publisher<'a>,
     // subscriber
-    _subscriber_gate_mode: Option<Subscriber<'a, ()>>,
-    _subscriber_engage: Option<Subscriber<'a, ()>>,
-    _subscriber_gear_command: Option<Subscriber<'a, ()>>,
-    _subscriber_velocity: Option<Subscriber<'a, ()>>,
+    _subscriber_gate_mode: Option<Subscription>,
+    _subscriber_engage: Option<Subscription>,
+    _subscriber_gear_command: Option<Subscription>,
+    _subscriber_control_mode: Option<Subscription>,
+    _subscriber_velocity: Option<Subscription>,
+    _subscriber_steering: Option<Subscription>,
+    _subscriber_operation_mode: Option<Subscription>,
+    _subscriber_route_state: Option<Subscription>,
+    _subscriber_localization_state: Option<Subscription>,
+    _subscriber_mrm_state: Option<Subscription>,
+    _subscriber_hazard_status: Option<Subscription>,
     // settings
+    control_api: ControlApi,
+    settings: Settings,
     steering_tire_angle: Arc<AtomicF32>,
     target_velocity: Arc<AtomicF32>,
+    brake: Arc<AtomicF32>,
+    /// Runtime speed cap (m/s), infinite if none
+    speed_cap: Arc<AtomicF32>,
     // status
-    gate_mode: Arc<AtomicU8>,
-    current_engage: Arc<AtomicBool>,
-    gear_command: Arc<AtomicU8>,
-    current_velocity: Arc<AtomicF32>,
+    status: Arc<StatusTracker>,
+    faults: Arc<Mutex<Vec<Fault>>>,
+    last_engage: Mutex<Option<EngageResult>>,
+    vehicle_model: Arc<Mutex<VehicleModel>>,
+    cruise: Arc<Mutex<CruiseControl>>,
+    /// Waiting for the control thread to ramp down to a stop before switching to AUTO
+    handing_over: Arc<AtomicBool>,
+    // control thread
+    publish_policy: PublishPolicy,
+    running: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    control_thread: Option<JoinHandle<()>>,
 }
 
-impl<'a> ManualController<'a> {
-    pub fn new(z_session: Arc<Session>, ros2: bool, prefix: String) -> Self {
-        let prefix_rt = prefix.clone() + if ros2 { "" } else { "rt/" };
-        let key_client_engage = if ros2 {
-            prefix.clone() + "api/autoware/set/engage"
-        } else {
-            "api/autoware/set/engage".to_owned()
-        };
-        let key_gate_mode = prefix_rt.clone() + "control/gate_mode_cmd";
-        let key_gear_command = prefix_rt.clone() + "external/selected/gear_cmd";
-
-        let publisher_gate_mode = z_session.declare_publisher(key_gate_mode).res().unwrap();
-        let publisher_gear_command = z_session.declare_publisher(key_gear_command).res().unwrap();
+impl ManualController {
+    pub fn new(z_session: Arc<Session>, mode: Mode, prefix: String) -> Self {
+        Self::with_transport(Arc::new(ZenohTransport::new(z_session)), mode, prefix)
+    }
 
+    /// Use another `Transport` than a Zenoh session, e.g. `MockTransport` in tests.
+    pub fn with_transport(transport: Arc<dyn Transport>, mode: Mode, prefix: String) -> Self {
         ManualController {
-            // mode
-            ros2,
-            // prefix
-            prefix,
-            // Session
-            z_session,
+            // key expressions
+            topics: Topics::new(mode, &prefix),
+            // Transport
+            transport,
             // GUID
             guid: rand::random::<i64>(),
             // service sequence
             sequence_number: Arc::new(AtomicU64::default()),
-            // service
-            key_client_engage,
-            // publisher
-            publisher_gate_mode,
-            publisher_gear_command,
             // subscriber
             _subscriber_gate_mode: None,
             _subscriber_engage: None,
             _subscriber_gear_command: None,
+            _subscriber_control_mode: None,
             _subscriber_velocity: None,
+            _subscriber_steering: None,
+            _subscriber_operation_mode: None,
+            _subscriber_route_state: None,
+            _subscriber_localization_state: None,
+            _subscriber_mrm_state: None,
+            _subscriber_hazard_status: None,
             // settings
+            control_api: ControlApi::Legacy,
+            settings: Settings::default(),
             steering_tire_angle: Arc::new(AtomicF32::new(0.0)),
             target_velocity: Arc::new(AtomicF32::new(0.0)),
+            brake: Arc::new(AtomicF32::new(0.0)),
+            speed_cap: Arc::new(AtomicF32::new(f32::INFINITY)),
             // status
-            gate_mode: Arc::new(AtomicU8::new(0)),
-            current_engage: Arc::new(AtomicBool::new(false)),
-            gear_command: Arc::new(AtomicU8::new(0)),
-            current_velocity: Arc::new(AtomicF32::new(0.0)),
+            status: Arc::new(StatusTracker::new()),
+            faults: Arc::new(Mutex::new(Vec::new())),
+            last_engage: Mutex::new(None),
+            vehicle_model: Arc::new(Mutex::new(VehicleModel::new(
+                Settings::default().vehicle.wheelbase,
+            ))),
+            cruise: Arc::new(Mutex::new(CruiseControl::new())),
+            handing_over: Arc::new(AtomicBool::new(false)),
+            // control thread
+            publish_policy: PublishPolicy::Always,
+            running: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            control_thread: None,
         }
     }
 
-    pub fn init(&mut self, z_session: Arc<Session>) {
-        let prefix_rt = self.prefix.clone() + if self.ros2 { "" } else { "rt/" };
-        let key_gate_mode = prefix_rt.clone() + "control/current_gate_mode";
-        let key_engage = prefix_rt.clone() + "api/autoware/get/engage";
-        let key_gear_command = prefix_rt.clone() + "vehicle/status/gear_status";
-        let key_velocity = prefix_rt.clone() + "vehicle/status/velocity_status";
-        let key_control_command = prefix_rt.clone() + "external/selected/control_cmd";
-
-        let gate_mode = self.gate_mode.clone();
-        self._subscriber_gate_mode = Some(
-            z_session
-                .declare_subscriber(key_gate_mode)
-                .callback_mut(move |sample| {
-                    match cdr::deserialize_from::<_, tier4_control_msgs::GateMode, _>(
-                        &*sample.payload.contiguous(),
-                        cdr::size::Infinite,
-                    ) {
-                        Ok(gatemode) => {
-                            log::debug!("Subscribe gatemode.data={}\r", gatemode.data);
-                            gate_mode.store(gatemode.data, Ordering::Relaxed);
-                        }
-                        Err(_) => {}
-                    }
-                })
-                .res()
-                .unwrap(),
-        );
-        let current_engage = self.current_engage.clone();
-        self._subscriber_engage = Some(
-            z_session
-                .declare_subscriber(key_engage)
-                .callback_mut(move |sample| {
-                    match cdr::deserialize_from::<_, autoware_auto_vehicle_msgs::Engage, _>(
-                        &*sample.payload.contiguous(),
-                        cdr::size::Infinite,
-                    ) {
-                        Ok(engage) => {
-                            log::debug!("Subscribe Engage: {}\r", engage.enable);
-                            current_engage.store(engage.enable, Ordering::Relaxed);
-                        }
-                        Err(_) => {}
-                    }
-                })
-                .res()
-                .unwrap(),
-        );
-        let gear_cmd = self.gear_command.clone();
-        self._subscriber_gear_command = Some(
-            z_session
-                .declare_subscriber(key_gear_command)
-                .callback_mut(move |sample| {
-                    match cdr::deserialize_from::<_, autoware_auto_vehicle_msgs::GearCommand, _>(
-                        &*sample.payload.contiguous(),
-                        cdr::size::Infinite,
-                    ) {
-                        Ok(gearcmd) => {
-                            log::debug!("Subscribe GearCommand: {}\r", gearcmd.command);
-                            gear_cmd.store(gearcmd.command, Ordering::Relaxed);
-                        }
-                        Err(_) => {}
-                    }
-                })
-                .res()
-                .unwrap(),
-        );
-        let current_velocity = self.current_velocity.clone();
-        self._subscriber_velocity = Some(
-            z_session
-                .declare_subscriber(key_velocity)
-                .callback_mut(move |sample| {
-                    match cdr::deserialize_from::<_, autoware_auto_vehicle_msgs::VelocityReport, _>(
-                        &*sample.payload.contiguous(),
-                        cdr::size::Infinite,
-                    ) {
-                        Ok(velocity) => {
-                            log::debug!(
-                                "Subscribe VelocityReport: {}\r",
-                                velocity.longitudinal_velocity
-                            );
-                            current_velocity
-                                .store(velocity.longitudinal_velocity, Ordering::Relaxed);
-                        }
-                        Err(_) => {}
-                    }
-                })
-                .res()
-                .unwrap(),
-        );
+    pub fn topics(&self) -> &Topics {
+        &self.topics
+    }
 
-        let steering_tire_angle = self.steering_tire_angle.clone();
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Take effect on the next `start`.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.vehicle_model
+            .lock()
+            .unwrap()
+            .set_wheelbase(settings.vehicle.wheelbase);
+        self.settings = settings;
+    }
+
+    /// Take effect on the next `start`.
+    pub fn set_message_set(&mut self, message_set: MessageSet) {
+        self.topics.message_set = message_set;
+    }
+
+    /// Take effect on the next `start`.
+    pub fn set_command_source(&mut self, source: CommandSource) {
+        self.topics = self.topics.clone().with_command_source(source);
+    }
+
+    pub fn command_source(&self) -> CommandSource {
+        self.topics.command_source
+    }
+
+    /// Take effect on the next `init`.
+    pub fn set_control_api(&mut self, api: ControlApi) {
+        self.control_api = api;
+    }
+
+    pub fn control_api(&self) -> ControlApi {
+        self.control_api
+    }
+
+    /// Declare the status subscribers and start the control thread.
+    /// Return false if the controller has already been initialized.
+    pub fn init(&mut self) -> bool {
+        if self._subscriber_gear_command.is_some() {
+            log::warn!("ManualController has already been initialized\r");
+            return false;
+        }
+
+        match self.control_api {
+            ControlApi::Legacy => {
+                let status = self.status.clone();
+                self._subscriber_gate_mode = Some(subscribe(
+                    self.transport.as_ref(),
+                    &self.topics.current_gate_mode,
+                    move |gatemode: tier4_control_msgs::GateMode| {
+                        log::debug!("Subscribe gatemode.data={}\r", gatemode.data);
+                        status.update(|s| s.gate_mode = gatemode.data);
+                    },
+                ));
+                let status = self.status.clone();
+                self._subscriber_engage = Some(subscribe(
+                    self.transport.as_ref(),
+                    &self.topics.engage_status,
+                    move |engage: autoware_auto_vehicle_msgs::Engage| {
+                        log::debug!("Subscribe Engage: {}\r", engage.enable);
+                        status.update(|s| s.engage = engage.enable);
+                    },
+                ));
+            }
+            ControlApi::AdApi => {
+                // LOCAL and REMOTE show up as the EXTERNAL gate mode, the rest as AUTO
+                let status = self.status.clone();
+                self._subscriber_operation_mode = Some(subscribe(
+                    self.transport.as_ref(),
+                    &self.topics.operation_mode_state,
+                    move |state: ad_api::OperationModeState| {
+                        log::debug!("Subscribe OperationModeState: {}\r", state.mode);
+                        let gate_mode = match state.mode {
+                            operation_mode::LOCAL | operation_mode::REMOTE => {
+                                tier4_control_msgs::gate_mode_data::EXTERNAL
+                            }
+                            _ => tier4_control_msgs::gate_mode_data::AUTO,
+                        };
+                        status.update(|s| {
+                            s.operation_mode = Some(state.mode);
+                            s.gate_mode = gate_mode;
+                            s.engage = state.is_autoware_control_enabled;
+                        });
+                    },
+                ));
+            }
+        }
+        let status = self.status.clone();
+        self._subscriber_gear_command = Some(subscribe(
+            self.transport.as_ref(),
+            &self.topics.gear_status,
+            move |gearcmd: autoware_auto_vehicle_msgs::GearCommand| {
+                log::debug!("Subscribe GearCommand: {}\r", gearcmd.command);
+                status.update(|s| s.gear = gearcmd.command);
+            },
+        ));
+        let status = self.status.clone();
         let target_velocity = self.target_velocity.clone();
-        let gear_cmd = self.gear_command.clone();
-        let current_velocity = self.current_velocity.clone();
-        let publisher_control_command = z_session
-            .declare_publisher(key_control_command)
-            .res()
-            .unwrap();
-        thread::spawn(move || {
-            loop {
+        let cruise = self.cruise.clone();
+        self._subscriber_control_mode = Some(subscribe(
+            self.transport.as_ref(),
+            &self.topics.control_mode,
+            move |report: autoware_auto_vehicle_msgs::ControlModeReport| {
+                log::debug!("Subscribe ControlModeReport: {}\r", report.mode);
+                status.update(|s| s.control_mode = Some(report.mode));
+                if status.status().driver_override() {
+                    // Start from a stop when the vehicle is handed back
+                    target_velocity.store(0.0, Ordering::Relaxed);
+                    cruise.lock().unwrap().cancel();
+                }
+            },
+        ));
+        let status = self.status.clone();
+        let vehicle_model = self.vehicle_model.clone();
+        self._subscriber_velocity = Some(subscribe(
+            self.transport.as_ref(),
+            &self.topics.velocity_status,
+            move |velocity: autoware_auto_vehicle_msgs::VelocityReport| {
                 log::debug!(
-                    "target velocity:{}, target angle:{}\r",
-                    target_velocity.load(Ordering::Relaxed),
-                    steering_tire_angle.load(Ordering::Relaxed)
+                    "Subscribe VelocityReport: {}\r",
+                    velocity.longitudinal_velocity
                 );
-                let real_target_velocity = target_velocity.load(Ordering::Relaxed)
-                    * (if gear_cmd.load(Ordering::Relaxed)
-                        == autoware_auto_vehicle_msgs::gear_command::DRIVE
-                    {
-                        1.0
-                    } else {
-                        -1.0
-                    });
-                let acceleration = num::clamp(
-                    target_velocity.load(Ordering::Relaxed)
-                        - current_velocity.load(Ordering::Relaxed).abs(),
-                    -1.0,
-                    1.0,
+                let pose = vehicle_model.lock().unwrap().update(
+                    velocity.longitudinal_velocity,
+                    status.status().steering_tire_angle,
+                    Instant::now(),
                 );
-                let current_time = SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap();
-                let ros_time = builtin_interfaces::Time {
-                    sec: current_time.as_secs() as i32,
-                    nanosec: current_time.subsec_nanos(),
-                };
-                let control_cmd = autoware_auto_control_msgs::AckermannControlCommand {
-                    stamp: ros_time.clone(),
-                    lateral: autoware_auto_control_msgs::AckermannLateralCommand {
-                        stamp: ros_time.clone(),
-                        steering_tire_angle: steering_tire_angle.load(Ordering::Relaxed),
-                        steering_tire_rotation_rate: 0.0,
-                    },
-                    longitudinal: autoware_auto_control_msgs::LongitudinalCommand {
-                        stamp: ros_time.clone(),
-                        speed: real_target_velocity,
-                        acceleration,
-                        jerk: 0.0,
-                    },
-                };
-                let encoded = cdr::serialize::<_, _, CdrLe>(&control_cmd, Infinite).unwrap();
-                publisher_control_command.put(encoded).res().unwrap();
-                thread::sleep(Duration::from_millis(33)); // 30 Hz
-            }
-        });
+                status.update(|s| {
+                    s.current_velocity = velocity.longitudinal_velocity;
+                    s.pose = pose;
+                });
+            },
+        ));
+        let status = self.status.clone();
+        self._subscriber_steering = Some(subscribe(
+            self.transport.as_ref(),
+            &self.topics.steering_status,
+            move |steering: autoware_auto_vehicle_msgs::SteeringReport| {
+                log::debug!(
+                    "Subscribe SteeringReport: {}\r",
+                    steering.steering_tire_angle
+                );
+                status.update(|s| s.steering_tire_angle = steering.steering_tire_angle);
+            },
+        ));
+        let status = self.status.clone();
+        self._subscriber_route_state = Some(subscribe(
+            self.transport.as_ref(),
+            &self.topics.route_state,
+            move |route: routing::RouteState| {
+                log::debug!("Subscribe RouteState: {}\r", route.state);
+                status.update(|s| s.route_state = Some(route.state));
+            },
+        ));
+        let status = self.status.clone();
+        self._subscriber_localization_state = Some(subscribe(
+            self.transport.as_ref(),
+            &self.topics.localization_initialization_state,
+            move |state: localization::LocalizationInitializationState| {
+                log::debug!(
+                    "Subscribe LocalizationInitializationState: {}\r",
+                    state.state
+                );
+                status.update(|s| s.localization_state = Some(state.state));
+            },
+        ));
+        let status = self.status.clone();
+        self._subscriber_mrm_state = Some(subscribe(
+            self.transport.as_ref(),
+            &self.topics.mrm_state,
+            move |mrm: diagnostics::MrmState| {
+                log::debug!("Subscribe MrmState: {} {}\r", mrm.state, mrm.behavior);
+                status.update(|s| {
+                    s.mrm = Some(Mrm {
+                        state: mrm.state,
+                        behavior: mrm.behavior,
+                    })
+                });
+            },
+        ));
+        let status = self.status.clone();
+        let faults = self.faults.clone();
+        self._subscriber_hazard_status = Some(subscribe(
+            self.transport.as_ref(),
+            &self.topics.hazard_status,
+            move |hazard: diagnostics::HazardStatusStamped| {
+                log::debug!("Subscribe HazardStatus: {}\r", hazard.status.level);
+                *faults.lock().unwrap() = hazard.status.faults();
+                status.update(|s| s.hazard_level = Some(hazard.status.level));
+            },
+        ));
+
+        self.start()
     }
 
-    fn pub_gate_mode(&self, mode: u8) {
-        let gate_mode_data = tier4_control_msgs::GateMode { data: mode };
-        let encoded = cdr::serialize::<_, _, CdrLe>(&gate_mode_data, Infinite).unwrap();
-        self.publisher_gate_mode.put(encoded).res().unwrap();
-    }
-
-    fn send_client_engage(&self) {
-        if self.ros2 {
-            let engage_data = true;
-            let encoded = cdr::serialize::<_, _, CdrLe>(&engage_data, Infinite).unwrap();
-            let replies = self
-                .z_session
-                .get(&self.key_client_engage)
-                .with_value(encoded)
-                .res()
-                .unwrap();
-            match replies.recv() {
-                Ok(reply) => match reply.sample {
-                    Ok(sample) => {
-                        match cdr::deserialize_from::<_, tier4_external_api_msgs::EngageResponse, _>(
-                            &*sample.payload.contiguous(),
-                            cdr::size::Infinite,
-                        ) {
-                            Ok(engage) => {
-                                log::info!(
-                                    "Engage Received ('code: {}, message: {}')\r",
-                                    engage.status.code,
-                                    engage.status.message
-                                );
-                            }
-                            Err(err) => {
-                                log::error!("Unable to deserialize engage message: {:?}\r", err)
-                            }
-                        }
+    /// Take effect on the next `start`.
+    pub fn set_publish_policy(&mut self, policy: PublishPolicy) {
+        self.publish_policy = policy;
+    }
+
+    /// Spawn the thread publishing control commands at 30 Hz.
+    /// Return false if the thread is already running.
+    pub fn start(&mut self) -> bool {
+        if self.running.swap(true, Ordering::Relaxed) {
+            log::warn!("Control thread is already running\r");
+            return false;
+        }
+        let publish_policy = self.publish_policy;
+        let settings = self.settings.clone();
+        let brake = self.brake.clone();
+        let speed_cap = self.speed_cap.clone();
+        let running = self.running.clone();
+        let paused = self.paused.clone();
+        let steering_tire_angle = self.steering_tire_angle.clone();
+        let target_velocity = self.target_velocity.clone();
+        let status = self.status.clone();
+        let cruise = self.cruise.clone();
+        let transport = self.transport.clone();
+        let topics = self.topics.clone();
+        let handing_over = self.handing_over.clone();
+        let control_api = self.control_api;
+        let guid = self.guid;
+        let sequence_number = self.sequence_number.clone();
+        let control_settings = settings.control.clone();
+        self.control_thread = Some(thread::spawn(move || {
+            let mut control_loop = ControlLoop::with_settings(publish_policy, settings);
+            let mut last_gate_mode = None;
+            while running.load(Ordering::Relaxed) {
+                status.check_link();
+                if !paused.load(Ordering::Relaxed) {
+                    let current = status.status();
+                    if last_gate_mode == Some(tier4_control_msgs::gate_mode_data::EXTERNAL)
+                        && current.gate_mode == tier4_control_msgs::gate_mode_data::AUTO
+                        && control_settings.targets_on_auto == TargetPolicy::Reset
+                    {
+                        log::info!("Back to AUTO, reset the targets\r");
+                        cruise.lock().unwrap().cancel();
+                        target_velocity.store(0.0, Ordering::Relaxed);
+                        steering_tire_angle.store(0.0, Ordering::Relaxed);
                     }
-                    Err(err) => log::error!("Engage Received (ERROR: '{:?}')\r", err),
-                },
-                Err(err) => {
-                    log::error!("Failed to send engage query {:?}!\r", err);
-                }
-            }
-        } else {
-            let seq = self.sequence_number.fetch_add(1, Ordering::Relaxed);
-            log::info!("Sending Engage: guid={}, seq={}\r", self.guid as u64, seq);
-            let engage_data = tier4_external_api_msgs::RawEngageRequest {
-                header: service::ServiceHeader {
-                    guid: self.guid,
-                    seq,
-                },
-                mode: true,
-            };
-            let request_key = self.prefix.clone() + "rq/" + &self.key_client_engage + "Request";
-            let reply_key = self.prefix.clone() + "rr/" + &self.key_client_engage + "Reply";
-            let subscriber = self.z_session.declare_subscriber(&reply_key).res().unwrap();
-            let encoded = cdr::serialize::<_, _, CdrLe>(&engage_data, Infinite).unwrap();
-            self.z_session.put(&request_key, encoded).res().unwrap();
-            match subscriber.recv() {
-                Ok(sample) => {
-                    match cdr::deserialize_from::<_, tier4_external_api_msgs::RawEngageResponse, _>(
-                        &*sample.payload.contiguous(),
-                        cdr::size::Infinite,
-                    ) {
-                        Ok(engage) => {
-                            log::info!(
-                                "Engage Received ('guid: {}, seq: {}, code: {}, message: {}')\r",
-                                engage.header.guid as u64,
-                                engage.header.seq,
-                                engage.status.code,
-                                engage.status.message
+                    last_gate_mode = Some(current.gate_mode);
+                    let cruise_target = {
+                        let mut cruise = cruise.lock().unwrap();
+                        if cruise.check_gear(current.gear) {
+                            log::info!("Gear changed, cancel cruise control\r");
+                            target_velocity.store(0.0, Ordering::Relaxed);
+                        }
+                        cruise.target()
+                    };
+                    let input = ControlInput {
+                        target_velocity: target_velocity.load(Ordering::Relaxed),
+                        steering_tire_angle: steering_tire_angle.load(Ordering::Relaxed),
+                        gate_mode: current.gate_mode,
+                        gear: current.gear,
+                        current_velocity: current.current_velocity,
+                        current_steering_tire_angle: current.steering_tire_angle,
+                        cruise_target,
+                        brake: brake.load(Ordering::Relaxed),
+                        speed_cap: Some(speed_cap.load(Ordering::Relaxed))
+                            .filter(|cap| cap.is_finite()),
+                        driver_override: current.driver_override(),
+                        handover: handing_over.load(Ordering::Relaxed),
+                    };
+                    let command = control_loop.step(&input);
+                    match control_loop.handover() {
+                        Handover::Done if handing_over.swap(false, Ordering::Relaxed) => {
+                            target_velocity.store(0.0, Ordering::Relaxed);
+                            hand_over_to_auto(
+                                transport.clone(),
+                                &topics,
+                                control_api,
+                                guid,
+                                &sequence_number,
                             );
                         }
-                        Err(err) => {
-                            log::error!("Unable to deserialize engage message: {:?}\r", err)
+                        Handover::Cancelled => handing_over.store(false, Ordering::Relaxed),
+                        _ => {}
+                    }
+                    if let Some(control_cmd) = command {
+                        match topics.command_source {
+                            CommandSource::Selected => publish_control_command(
+                                transport.as_ref(),
+                                &topics.control_cmd,
+                                topics.message_set,
+                                &control_cmd,
+                            ),
+                            CommandSource::Local | CommandSource::Remote => {
+                                let pedals =
+                                    external::pedal_command(&control_cmd, &control_settings);
+                                let heartbeat = external::Heartbeat {
+                                    stamp: pedals.stamp.clone(),
+                                };
+                                publish(transport.as_ref(), &topics.control_cmd, &pedals);
+                                publish(transport.as_ref(), &topics.heartbeat, &heartbeat);
+                            }
                         }
                     }
                 }
-                Err(err) => log::error!("Engage Received (ERROR: '{:?}')\r", err),
+                thread::sleep(CONTROL_PERIOD);
+            }
+        }));
+        true
+    }
+
+    /// Stop the control thread and wait for it to exit.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.control_thread.take() {
+            if handle.join().is_err() {
+                log::error!("Control thread panicked\r");
             }
         }
     }
 
+    /// Keep the control thread alive but stop publishing control commands.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    fn pub_gate_mode(&self, mode: u8) {
+        let gate_mode_data = tier4_control_msgs::GateMode { data: mode };
+        publish(
+            self.transport.as_ref(),
+            &self.topics.gate_mode_cmd,
+            &gate_mode_data,
+        );
+    }
+
+    /// Ask Autoware to engage. `toggle_gate_mode` does it when switching to EXTERNAL.
+    pub fn engage(&self) -> EngageResult {
+        let seq = self.sequence_number.fetch_add(1, Ordering::Relaxed);
+        let result = send_engage(self.transport.as_ref(), &self.topics.engage, self.guid, seq);
+        *self.last_engage.lock().unwrap() = Some(result.clone());
+        result
+    }
+
+    /// Result of the last engage request, None if none was sent
+    pub fn last_engage(&self) -> Option<EngageResult> {
+        self.last_engage.lock().unwrap().clone()
+    }
+
+    /// Ask Autoware to switch to the operation `mode` (see `ad_api::operation_mode`)
+    /// and return the error reported by the AD API, if any.
+    pub fn change_operation_mode(&self, mode: u8) -> Result<(), String> {
+        let seq = self.sequence_number.fetch_add(1, Ordering::Relaxed);
+        send_change_operation_mode(self.transport.as_ref(), &self.topics, self.guid, seq, mode)
+    }
+
+    /// Ask the external command selector of Autoware to listen to our command source,
+    /// and return its error, if any. Nothing to do with the selected source.
+    pub fn select_external_command(&self) -> Result<(), String> {
+        let mode = match self.topics.command_source {
+            CommandSource::Selected => return Ok(()),
+            CommandSource::Local => selector_mode::LOCAL,
+            CommandSource::Remote => selector_mode::REMOTE,
+        };
+        let seq = self.sequence_number.fetch_add(1, Ordering::Relaxed);
+        let response: external::ExternalCommandSelectResponse = crate::service::call(
+            self.transport.as_ref(),
+            &self.topics.select_external_command,
+            self.guid,
+            seq,
+            &external::ExternalCommandSelectRequest { mode },
+            SERVICE_TIMEOUT,
+        )?;
+        log::info!(
+            "Select {} external command ('success: {}, message: {}')\r",
+            self.topics.command_source.name(),
+            response.success,
+            response.message
+        );
+        if response.success {
+            Ok(())
+        } else {
+            Err(response.message)
+        }
+    }
+
+    /// Send the goal (map frame) to the routing API. A route already set is cleared first.
+    pub fn set_goal(&self, goal: &MapPose) -> Result<(), String> {
+        // Autoware rejects a new route while one is set, even once arrived
+        if self.status.status().route_state != Some(route_state::UNSET) {
+            self.clear_route()?;
+        }
+        let request = routing::SetRoutePointsRequest {
+            header: std_msgs::Header {
+                stamp: ros_time_now(),
+                frame_id: "map".to_owned(),
+            },
+            option: routing::RouteOption {
+                allow_goal_modification: self.settings.routing.allow_goal_modification,
+            },
+            goal: routing::ros_pose(goal),
+            waypoints: Vec::new(),
+        };
+        let seq = self.sequence_number.fetch_add(1, Ordering::Relaxed);
+        let response: ad_api::StatusResponse = crate::service::call(
+            self.transport.as_ref(),
+            &self.topics.set_route_points,
+            self.guid,
+            seq,
+            &request,
+            SERVICE_TIMEOUT,
+        )?;
+        log::info!(
+            "Set goal x:{} y:{} yaw:{} ('code: {}, message: {}')\r",
+            goal.x,
+            goal.y,
+            goal.yaw,
+            response.status.code,
+            response.status.message
+        );
+        response.status.into_result()
+    }
+
+    pub fn clear_route(&self) -> Result<(), String> {
+        let seq = self.sequence_number.fetch_add(1, Ordering::Relaxed);
+        let response: ad_api::StatusResponse = crate::service::call(
+            self.transport.as_ref(),
+            &self.topics.clear_route,
+            self.guid,
+            seq,
+            &ad_api::EmptyRequest::default(),
+            SERVICE_TIMEOUT,
+        )?;
+        log::info!(
+            "Clear route ('code: {}, message: {}')\r",
+            response.status.code,
+            response.status.message
+        );
+        response.status.into_result()
+    }
+
+    /// Initialize the localization at the pose (map frame), like the "2D Pose Estimate" of RViz.
+    pub fn initialize_localization(&self, pose: &MapPose) -> Result<(), String> {
+        let header = std_msgs::Header {
+            stamp: ros_time_now(),
+            frame_id: "map".to_owned(),
+        };
+        let request = localization::InitializeLocalizationRequest {
+            pose: vec![localization::initial_pose(pose, header)],
+        };
+        let seq = self.sequence_number.fetch_add(1, Ordering::Relaxed);
+        let response: ad_api::StatusResponse = crate::service::call(
+            self.transport.as_ref(),
+            &self.topics.initialize_localization,
+            self.guid,
+            seq,
+            &request,
+            SERVICE_TIMEOUT,
+        )?;
+        log::info!(
+            "Initialize localization x:{} y:{} yaw:{} ('code: {}, message: {}')\r",
+            pose.x,
+            pose.y,
+            pose.yaw,
+            response.status.code,
+            response.status.message
+        );
+        response.status.into_result()
+    }
+
+    /// Switch between AUTO and EXTERNAL, or between the AUTONOMOUS and LOCAL operation
+    /// modes with the AD API.
+    /// With a `handover_ramp_time`, the switch to AUTO waits for the control thread to
+    /// ramp down to a stop. Toggling again during the ramp cancels it and stays in EXTERNAL.
     pub fn toggle_gate_mode(&self) -> bool {
         // Return whether switch to external or not
-        if self.gate_mode.load(Ordering::Relaxed) == tier4_control_msgs::gate_mode_data::AUTO {
+        if self.handing_over.swap(false, Ordering::Relaxed) {
+            log::info!("Handover to AUTO cancelled, staying in EXTERNAL\r");
+            return true;
+        }
+        if self.control_api == ControlApi::AdApi {
+            let external =
+                self.status.status().gate_mode == tier4_control_msgs::gate_mode_data::AUTO;
+            let mode = if external && self.topics.command_source == CommandSource::Remote {
+                operation_mode::REMOTE
+            } else if external {
+                operation_mode::LOCAL
+            } else {
+                operation_mode::AUTONOMOUS
+            };
+            if external {
+                self.take_over();
+            } else if self.ramp_down() {
+                return false;
+            }
+            if let Err(err) = self.change_operation_mode(mode) {
+                log::error!(
+                    "Failed to change to {}: {}\r",
+                    ad_api::operation_mode_name(mode),
+                    err
+                );
+            }
+            return external;
+        }
+        if self.status.status().gate_mode == tier4_control_msgs::gate_mode_data::AUTO {
             // Auto => External
+            self.take_over();
+            if let Err(err) = self.select_external_command() {
+                log::error!("Failed to select the external command: {}\r", err);
+            }
             self.pub_gate_mode(tier4_control_msgs::gate_mode_data::EXTERNAL);
-            self.send_client_engage();
+            let _ = self.engage();
             true
         } else {
             // External => Auto
-            self.pub_gate_mode(tier4_control_msgs::gate_mode_data::AUTO);
+            if !self.ramp_down() {
+                self.pub_gate_mode(tier4_control_msgs::gate_mode_data::AUTO);
+            }
             false
         }
     }
 
+    /// Targets (speed, steering) the next switch to EXTERNAL would start from: the reported
+    /// motion with `bumpless_takeover`, else the current targets unless reset in AUTO
+    pub fn pending_targets(&self) -> (f32, f32) {
+        let status = self.status.status();
+        if !self.settings.control.bumpless_takeover {
+            if status.gate_mode == tier4_control_msgs::gate_mode_data::AUTO
+                && self.settings.control.targets_on_auto == TargetPolicy::Reset
+            {
+                return (0.0, 0.0);
+            }
+            return (self.target_velocity(), self.target_steering_tire_angle());
+        }
+        let input = ControlInput {
+            gear: status.gear,
+            current_velocity: status.current_velocity,
+            current_steering_tire_angle: status.steering_tire_angle,
+            speed_cap: self.speed_cap(),
+            ..Default::default()
+        };
+        ControlLoop::takeover_targets(&self.settings, &input)
+    }
+
+    /// Let the control thread ramp down to a stop over `handover_ramp_time` and switch to
+    /// AUTO when stopped. Return false if there is no ramp, i.e. switch right away.
+    fn ramp_down(&self) -> bool {
+        if self.settings.control.handover_ramp_time <= 0.0 || !self.is_running() || self.is_paused()
+        {
+            return false;
+        }
+        self.cancel_cruise();
+        self.handing_over.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// Whether the control thread is ramping down before switching to AUTO
+    pub fn handing_over(&self) -> bool {
+        self.handing_over.load(Ordering::Relaxed)
+    }
+
+    fn cancel_handover(&self) {
+        if self.handing_over.swap(false, Ordering::Relaxed) {
+            log::info!("Handover to AUTO cancelled by a new target\r");
+        }
+    }
+
+    /// Start from the reported motion rather than the last targets, if `bumpless_takeover`
+    fn take_over(&self) {
+        if !self.settings.control.bumpless_takeover {
+            return;
+        }
+        let (velocity, angle) = self.pending_targets();
+        log::info!("Take over at velocity:{}, angle:{}\r", velocity, angle);
+        self.cancel_cruise();
+        self.target_velocity.store(velocity, Ordering::Relaxed);
+        self.steering_tire_angle.store(angle, Ordering::Relaxed);
+    }
+
+    /// Changing the gear cancels the cruise control.
     pub fn pub_gear_command(&self, command: u8) {
+        if self.cruise.lock().unwrap().cancel() {
+            self.target_velocity.store(0.0, Ordering::Relaxed);
+        }
+        if self.topics.command_source != CommandSource::Selected {
+            let gear_shift = external::GearShiftStamped {
+                stamp: builtin_interfaces::Time { sec: 0, nanosec: 0 },
+                gear_shift: external::gear_shift_of(command),
+            };
+            publish(self.transport.as_ref(), &self.topics.gear_cmd, &gear_shift);
+            return;
+        }
         let gear_command = autoware_auto_vehicle_msgs::GearCommand {
             stamp: builtin_interfaces::Time { sec: 0, nanosec: 0 },
-            command: command,
+            command,
         };
-        let encoded = cdr::serialize::<_, _, CdrLe>(&gear_command, Infinite).unwrap();
-        self.publisher_gear_command.put(encoded).res().unwrap();
+        publish(
+            self.transport.as_ref(),
+            &self.topics.gear_cmd,
+            &gear_command,
+        );
     }
 
+    /// Set the target speed (m/s, always positive) and steering tire angle (radian).
+    /// Lowering the target speed acts as a brake and cancels the cruise control.
+    /// Ignored with a warning while the vehicle reports a driver override.
     pub fn update_control_command(&self, velocity: f32, angle: f32) {
+        if self.motion_blocked() {
+            log::warn!(
+                "The vehicle is in {} mode, ignoring the motion command\r",
+                self.status
+                    .status()
+                    .control_mode
+                    .map_or("Unknown", control_mode_name)
+            );
+            return;
+        }
+        self.cancel_handover();
+        if velocity < self.target_velocity.load(Ordering::Relaxed) {
+            self.cruise.lock().unwrap().cancel();
+        }
         self.steering_tire_angle.store(angle, Ordering::Relaxed);
         self.target_velocity.store(velocity, Ordering::Relaxed);
     }
 
+    /// Whether the vehicle reports a manual or disengaged control mode, in which case the
+    /// motion commands are blocked and the control loop commands a stop
+    pub fn motion_blocked(&self) -> bool {
+        self.status.status().driver_override()
+    }
+
+    /// Set the brake demand, from 0 (released) to 1 (full brake, `max_deceleration` in the
+    /// settings). Braking overrides the target speed, which is kept for when the brake is
+    /// released, and cancels the cruise control.
+    pub fn set_brake(&self, demand: f32) {
+        let demand = num::clamp(demand, 0.0, 1.0);
+        if demand > 0.0 {
+            self.cancel_cruise();
+        }
+        self.brake.store(demand, Ordering::Relaxed);
+    }
+
+    pub fn brake(&self) -> f32 {
+        self.brake.load(Ordering::Relaxed)
+    }
+
+    /// Cap the speed (m/s) below the limits of the settings, or lift the cap with None.
+    pub fn set_speed_cap(&self, cap: Option<f32>) {
+        self.speed_cap
+            .store(cap.unwrap_or(f32::INFINITY), Ordering::Relaxed);
+    }
+
+    pub fn speed_cap(&self) -> Option<f32> {
+        Some(self.speed_cap.load(Ordering::Relaxed)).filter(|cap| cap.is_finite())
+    }
+
+    /// Toggle the speed cap of the yard mode (`yard_speed` in the settings).
+    /// Return whether the yard mode is on.
+    pub fn toggle_yard_mode(&self) -> bool {
+        let yard = self.speed_cap() != Some(self.settings.control.yard_speed);
+        self.set_speed_cap(yard.then_some(self.settings.control.yard_speed));
+        yard
+    }
+
+    /// Speed limit (m/s) applied by the control loop in the reported gear
+    pub fn speed_limit(&self) -> f32 {
+        ControlLoop::speed_limit(
+            &self.settings.control,
+            self.status.status().gear,
+            self.speed_cap(),
+        )
+    }
+
+    /// Steering tire angle limit (radian) applied by the control loop at the reported speed
+    pub fn steering_limit(&self) -> f32 {
+        ControlLoop::steering_limit(&self.settings, self.current_velocity().abs())
+    }
+
+    /// Target speed (m/s) used while the cruise control is not active
+    pub fn target_velocity(&self) -> f32 {
+        self.target_velocity.load(Ordering::Relaxed)
+    }
+
+    /// Target steering tire angle (radian)
+    pub fn target_steering_tire_angle(&self) -> f32 {
+        self.steering_tire_angle.load(Ordering::Relaxed)
+    }
+
+    /// Hold `target` (m/s), or the current velocity if None. Return the speed held.
+    pub fn set_cruise(&self, target: Option<f32>) -> f32 {
+        let status = self.status.status();
+        let target = target.unwrap_or(status.current_velocity.abs());
+        self.cruise.lock().unwrap().set(target, status.gear);
+        // Keep the same speed if the cruise is cancelled by lowering the target
+        self.target_velocity.store(target, Ordering::Relaxed);
+        target
+    }
+
+    /// Hold the last cruise speed again. Return false if the cruise was never set.
+    pub fn resume_cruise(&self) -> bool {
+        let gear = self.status.status().gear;
+        let mut cruise = self.cruise.lock().unwrap();
+        let resumed = cruise.resume(gear);
+        if let Some(target) = cruise.target() {
+            self.target_velocity.store(target, Ordering::Relaxed);
+        }
+        resumed
+    }
+
+    /// Change the cruise speed (m/s) while it is active.
+    pub fn adjust_cruise(&self, delta: f32) {
+        let mut cruise = self.cruise.lock().unwrap();
+        cruise.adjust(delta);
+        if let Some(target) = cruise.target() {
+            self.target_velocity.store(target, Ordering::Relaxed);
+        }
+    }
+
+    /// Stop holding the speed and slow down to a stop. `resume_cruise` goes back to it.
+    pub fn cancel_cruise(&self) {
+        if self.cruise.lock().unwrap().cancel() {
+            self.target_velocity.store(0.0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn cruise(&self) -> CruiseState {
+        self.cruise.lock().unwrap().state()
+    }
+
+    /// Latest longitudinal velocity reported by the vehicle (m/s)
+    pub fn current_velocity(&self) -> f32 {
+        self.status.status().current_velocity
+    }
+
+    pub fn status(&self) -> ControllerStatus {
+        self.status.status()
+    }
+
+    /// Active faults of the last hazard status, the most severe first
+    pub fn faults(&self) -> Vec<Fault> {
+        self.faults.lock().unwrap().clone()
+    }
+
+    /// The tracker fed by the status subscriptions, e.g. for a `Replayer`
+    pub fn status_tracker(&self) -> Arc<StatusTracker> {
+        self.status.clone()
+    }
+
     pub fn get_status(&self) -> String {
-        let mut s = String::from("Enage:");
-        s += if self.current_engage.load(Ordering::Relaxed) {
-            "Ready"
-        } else {
-            "Not Ready"
-        };
-        s += "\tGate Mode:";
-        s += match self.gate_mode.load(Ordering::Relaxed) {
-            tier4_control_msgs::gate_mode_data::AUTO => "Auto",
-            tier4_control_msgs::gate_mode_data::EXTERNAL => "External",
-            _ => "Unknown",
-        };
-        s += "\tGear:";
-        s += match self.gear_command.load(Ordering::Relaxed) {
-            autoware_auto_vehicle_msgs::gear_command::DRIVE => "D",
-            autoware_auto_vehicle_msgs::gear_command::REVERSE => "R",
-            autoware_auto_vehicle_msgs::gear_command::PARK => "P",
-            autoware_auto_vehicle_msgs::gear_command::LOW => "L",
-            _ => "?",
+        let status = self.status.status();
+        let mut text = status.to_string();
+        let cruise = self.cruise();
+        if cruise != CruiseState::Off {
+            text += &format!("\tCruise:{}", cruise);
+        }
+        if status.gate_mode == tier4_control_msgs::gate_mode_data::AUTO {
+            let (velocity, angle) = self.pending_targets();
+            text += &format!(
+                "\tPending:{:.1} km/hr, {:.1} deg",
+                velocity * 3.6,
+                angle.to_degrees()
+            );
+        }
+        text
+    }
+
+    /// Call `listener` whenever the gate mode, engage, gear, velocity thresholds or link health change.
+    pub fn on_status_change(&self, listener: impl Fn(&StatusEvent) + Send + 'static) {
+        self.status.on_change(listener);
+    }
+
+    /// Receive the status changes through a channel instead of a callback.
+    pub fn subscribe_status(&self) -> Receiver<StatusEvent> {
+        self.status.subscribe()
+    }
+
+    /// Path the vehicle would follow in the next `horizon` seconds at the reported velocity
+    /// and steering angle, in the vehicle frame
+    pub fn predicted_path(&self, horizon: f32) -> Vec<Pose> {
+        let status = self.status.status();
+        self.vehicle_model.lock().unwrap().predict_path(
+            status.current_velocity,
+            status.steering_tire_angle,
+            horizon,
+        )
+    }
+
+    /// Restart dead-reckoning from the origin.
+    pub fn reset_pose(&self) {
+        self.vehicle_model.lock().unwrap().reset();
+        self.status.update(|s| s.pose = Pose::default());
+    }
+
+    /// Velocities (m/s) whose crossing emits `StatusEvent::VelocityThresholdCrossed`
+    pub fn set_velocity_thresholds(&self, thresholds: Vec<f32>) {
+        self.status.set_velocity_thresholds(thresholds);
+    }
+}
+
+impl Drop for ManualController {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Ask Autoware to engage through the engage service, logging the response.
+/// `guid` and `seq` identify the request when the service is bridged as DDS topics.
+pub(crate) fn send_engage(
+    transport: &dyn Transport,
+    service: &ServiceKey,
+    guid: i64,
+    seq: u64,
+) -> EngageResult {
+    log::info!("Sending Engage: guid={}, seq={}\r", guid as u64, seq);
+    let result = crate::service::call::<_, tier4_external_api_msgs::EngageResponse>(
+        transport,
+        service,
+        guid,
+        seq,
+        &true,
+        ENGAGE_TIMEOUT,
+    )
+    .map_err(EngageError::NoResponse)
+    .and_then(|engage| engage_result(engage.status));
+    match &result {
+        Ok(status) => log::info!("Engage Received ({})\r", status),
+        Err(err) => log::error!("Engage failed: {}\r", err),
+    }
+    result
+}
+
+/// Ask Autoware to switch to the operation `mode` (see `ad_api::operation_mode`) through the
+/// AD API and return the error it reports, if any.
+pub(crate) fn send_change_operation_mode(
+    transport: &dyn Transport,
+    topics: &Topics,
+    guid: i64,
+    seq: u64,
+    mode: u8,
+) -> Result<(), String> {
+    let service = topics
+        .change_operation_mode(mode)
+        .ok_or_else(|| format!("No service to change to operation mode {}", mode))?;
+    let response: ad_api::StatusResponse = crate::service::call(
+        transport,
+        service,
+        guid,
+        seq,
+        &ad_api::EmptyRequest::default(),
+        SERVICE_TIMEOUT,
+    )?;
+    log::info!(
+        "Change to {} ('code: {}, message: {}')\r",
+        ad_api::operation_mode_name(mode),
+        response.status.code,
+        response.status.message
+    );
+    response.status.into_result()
+}
+
+/// Switch to AUTO at the end of a handover ramp. The AD API call runs in its own thread,
+/// so the control loop keeps holding the stop while waiting for the response.
+fn hand_over_to_auto(
+    transport: Arc<dyn Transport>,
+    topics: &Topics,
+    control_api: ControlApi,
+    guid: i64,
+    sequence_number: &AtomicU64,
+) {
+    log::info!("Stopped, handing over to AUTO\r");
+    if control_api == ControlApi::Legacy {
+        let gate_mode = tier4_control_msgs::GateMode {
+            data: tier4_control_msgs::gate_mode_data::AUTO,
         };
-        s
+        publish(transport.as_ref(), &topics.gate_mode_cmd, &gate_mode);
+        return;
     }
+    let topics = topics.clone();
+    let seq = sequence_number.fetch_add(1, Ordering::Relaxed);
+    thread::spawn(move || {
+        let mode = operation_mode::AUTONOMOUS;
+        if let Err(err) = send_change_operation_mode(transport.as_ref(), &topics, guid, seq, mode) {
+            log::error!(
+                "Failed to change to {}: {}\r",
+                ad_api::operation_mode_name(mode),
+                err
+            );
+        }
+    });
 }
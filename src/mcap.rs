@@ -0,0 +1,345 @@
+//! Minimal writer and reader of unchunked [MCAP](https://mcap.dev/spec) files
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+pub const MAGIC: &[u8] = b"\x89MCAP0\r\n";
+
+pub(crate) mod op {
+    pub const HEADER: u8 = 0x01;
+    pub const FOOTER: u8 = 0x02;
+    pub const SCHEMA: u8 = 0x03;
+    pub const CHANNEL: u8 = 0x04;
+    pub const MESSAGE: u8 = 0x05;
+    pub const DATA_END: u8 = 0x0F;
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn put_bytes(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(data);
+}
+
+fn put_map(buf: &mut Vec<u8>, entries: &[(&str, &str)]) {
+    let mut map = Vec::new();
+    for (key, value) in entries {
+        put_string(&mut map, key);
+        put_string(&mut map, value);
+    }
+    put_bytes(buf, &map);
+}
+
+/// Writes the data section record by record, then repeats the schemas and channels in the
+/// summary section so readers can list them without scanning the whole file.
+/// CRCs are left to zero, which the spec defines as "not computed".
+pub struct McapWriter<W: Write> {
+    out: W,
+    position: u64,
+    next_schema_id: u16,
+    next_channel_id: u16,
+    summary: Vec<(u8, Vec<u8>)>,
+}
+
+impl<W: Write> McapWriter<W> {
+    pub fn new(out: W, profile: &str, library: &str) -> io::Result<Self> {
+        let mut writer = McapWriter {
+            out,
+            position: 0,
+            next_schema_id: 1,
+            next_channel_id: 0,
+            summary: Vec::new(),
+        };
+        writer.write_raw(MAGIC)?;
+        let mut header = Vec::new();
+        put_string(&mut header, profile);
+        put_string(&mut header, library);
+        writer.write_record(op::HEADER, &header)?;
+        Ok(writer)
+    }
+
+    fn write_raw(&mut self, data: &[u8]) -> io::Result<()> {
+        self.out.write_all(data)?;
+        self.position += data.len() as u64;
+        Ok(())
+    }
+
+    fn write_record(&mut self, opcode: u8, content: &[u8]) -> io::Result<()> {
+        self.write_raw(&[opcode])?;
+        self.write_raw(&(content.len() as u64).to_le_bytes())?;
+        self.write_raw(content)
+    }
+
+    /// Return the id to pass to `add_channel`. Id 0 is reserved for schemaless channels.
+    pub fn add_schema(&mut self, name: &str, encoding: &str, data: &[u8]) -> io::Result<u16> {
+        let id = self.next_schema_id;
+        self.next_schema_id += 1;
+        let mut schema = id.to_le_bytes().to_vec();
+        put_string(&mut schema, name);
+        put_string(&mut schema, encoding);
+        put_bytes(&mut schema, data);
+        self.write_record(op::SCHEMA, &schema)?;
+        self.summary.push((op::SCHEMA, schema));
+        Ok(id)
+    }
+
+    pub fn add_channel(
+        &mut self,
+        schema_id: u16,
+        topic: &str,
+        message_encoding: &str,
+        metadata: &[(&str, &str)],
+    ) -> io::Result<u16> {
+        let id = self.next_channel_id;
+        self.next_channel_id += 1;
+        let mut channel = id.to_le_bytes().to_vec();
+        channel.extend_from_slice(&schema_id.to_le_bytes());
+        put_string(&mut channel, topic);
+        put_string(&mut channel, message_encoding);
+        put_map(&mut channel, metadata);
+        self.write_record(op::CHANNEL, &channel)?;
+        self.summary.push((op::CHANNEL, channel));
+        Ok(id)
+    }
+
+    /// `log_time` and `publish_time` are nanoseconds since the Unix epoch.
+    pub fn write_message(
+        &mut self,
+        channel_id: u16,
+        sequence: u32,
+        log_time: u64,
+        publish_time: u64,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let mut message = Vec::with_capacity(22 + data.len());
+        message.extend_from_slice(&channel_id.to_le_bytes());
+        message.extend_from_slice(&sequence.to_le_bytes());
+        message.extend_from_slice(&log_time.to_le_bytes());
+        message.extend_from_slice(&publish_time.to_le_bytes());
+        message.extend_from_slice(data);
+        self.write_record(op::MESSAGE, &message)
+    }
+
+    /// Write the summary section and the footer, and hand back the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_record(op::DATA_END, &0u32.to_le_bytes())?;
+        let summary_start = self.position;
+        for (opcode, content) in std::mem::take(&mut self.summary) {
+            self.write_record(opcode, &content)?;
+        }
+        let mut footer = summary_start.to_le_bytes().to_vec();
+        footer.extend_from_slice(&0u64.to_le_bytes()); // no summary offsets
+        footer.extend_from_slice(&0u32.to_le_bytes()); // summary CRC
+        self.write_record(op::FOOTER, &footer)?;
+        self.write_raw(MAGIC)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid MCAP: {}", what),
+    )
+}
+
+/// Cursor over the content of a record
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid("truncated record"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Next complete record as (opcode, content)
+    fn record(&mut self) -> Option<(u8, Fields<'a>)> {
+        let opcode = self.take(1).ok()?[0];
+        let len = self.u64().ok()?;
+        Some((opcode, Fields(self.take(len as usize).ok()?)))
+    }
+
+    fn bytes(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| invalid("string is not UTF-8"))
+    }
+
+    fn map(&mut self) -> io::Result<HashMap<String, String>> {
+        let mut entries = Fields(self.bytes()?);
+        let mut map = HashMap::new();
+        while !entries.0.is_empty() {
+            map.insert(entries.string()?, entries.string()?);
+        }
+        Ok(map)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub id: u16,
+    pub schema_id: u16,
+    pub topic: String,
+    pub message_encoding: String,
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub channel_id: u16,
+    pub sequence: u32,
+    /// Nanoseconds since the Unix epoch
+    pub log_time: u64,
+    pub publish_time: u64,
+    pub data: Vec<u8>,
+}
+
+/// Channels and messages of the data section of an unchunked MCAP file.
+/// Chunks and other records are skipped.
+#[derive(Debug, Default)]
+pub struct McapReader {
+    pub channels: HashMap<u16, Channel>,
+    /// In file order
+    pub messages: Vec<Message>,
+}
+
+impl McapReader {
+    pub fn parse(file: &[u8]) -> io::Result<Self> {
+        if !file.starts_with(MAGIC) {
+            return Err(invalid("bad magic"));
+        }
+        let mut reader = McapReader::default();
+        let mut rest = Fields(&file[MAGIC.len()..]);
+        // An unfinished file ends without a footer, possibly in the middle of a record.
+        // Keep what was fully written.
+        while let Some((opcode, mut record)) = rest.record() {
+            match opcode {
+                op::CHANNEL => {
+                    let channel = Channel {
+                        id: record.u16()?,
+                        schema_id: record.u16()?,
+                        topic: record.string()?,
+                        message_encoding: record.string()?,
+                        metadata: record.map()?,
+                    };
+                    reader.channels.insert(channel.id, channel);
+                }
+                op::MESSAGE => reader.messages.push(Message {
+                    channel_id: record.u16()?,
+                    sequence: record.u32()?,
+                    log_time: record.u64()?,
+                    publish_time: record.u64()?,
+                    data: record.0.to_vec(),
+                }),
+                op::DATA_END | op::FOOTER => break,
+                _ => {}
+            }
+        }
+        Ok(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(file: &[u8]) -> Vec<(u8, &[u8])> {
+        let mut records = Vec::new();
+        let mut rest = &file[MAGIC.len()..file.len() - MAGIC.len()];
+        while !rest.is_empty() {
+            let len = u64::from_le_bytes(rest[1..9].try_into().unwrap()) as usize;
+            records.push((rest[0], &rest[9..9 + len]));
+            rest = &rest[9 + len..];
+        }
+        records
+    }
+
+    #[test]
+    fn file_layout() {
+        let mut writer = McapWriter::new(Vec::new(), "ros2", "test").unwrap();
+        let schema = writer
+            .add_schema("pkg/msg/T", "ros2msg", b"uint8 data")
+            .unwrap();
+        let channel = writer.add_channel(schema, "/t", "cdr", &[]).unwrap();
+        writer.write_message(channel, 0, 1, 1, &[7]).unwrap();
+        let file = writer.finish().unwrap();
+
+        assert!(file.starts_with(MAGIC) && file.ends_with(MAGIC));
+        let opcodes: Vec<u8> = records(&file).iter().map(|(opcode, _)| *opcode).collect();
+        assert_eq!(
+            opcodes,
+            [
+                op::HEADER,
+                op::SCHEMA,
+                op::CHANNEL,
+                op::MESSAGE,
+                op::DATA_END,
+                op::SCHEMA,
+                op::CHANNEL,
+                op::FOOTER
+            ]
+        );
+        let message = records(&file)[3].1;
+        assert_eq!(message.len(), 23);
+        assert_eq!(message[22], 7);
+    }
+
+    #[test]
+    fn read_back() {
+        let mut writer = McapWriter::new(Vec::new(), "ros2", "test").unwrap();
+        let channel = writer.add_channel(0, "/t", "cdr", &[("k", "v")]).unwrap();
+        writer.write_message(channel, 3, 10, 11, &[1, 2]).unwrap();
+        let file = writer.finish().unwrap();
+
+        let reader = McapReader::parse(&file).unwrap();
+        assert_eq!(reader.channels[&channel].topic, "/t");
+        assert_eq!(reader.channels[&channel].metadata["k"], "v");
+        assert_eq!(
+            reader.messages,
+            [Message {
+                channel_id: channel,
+                sequence: 3,
+                log_time: 10,
+                publish_time: 11,
+                data: vec![1, 2],
+            }]
+        );
+    }
+
+    #[test]
+    fn read_unfinished_file() {
+        let mut file = Vec::new();
+        let mut writer = McapWriter::new(&mut file, "ros2", "test").unwrap();
+        let channel = writer.add_channel(0, "/t", "cdr", &[]).unwrap();
+        writer.write_message(channel, 0, 1, 1, &[1]).unwrap();
+        writer.write_message(channel, 1, 2, 2, &[2]).unwrap();
+        drop(writer);
+        file.truncate(file.len() - 1);
+
+        let reader = McapReader::parse(&file).unwrap();
+        assert_eq!(reader.messages.len(), 1);
+    }
+}
@@ -0,0 +1,147 @@
+use cdr::{CdrLe, Infinite};
+use clap::ValueEnum;
+use serde_derive::{Deserialize, Serialize};
+use zenoh_ros_type::{
+    autoware_auto_control_msgs::{
+        AckermannControlCommand, AckermannLateralCommand, LongitudinalCommand,
+    },
+    builtin_interfaces,
+};
+
+use crate::transport::Transport;
+
+/// Which Autoware message packages the vehicle speaks.
+/// Both sets share the layout of the vehicle messages (gear, velocity, steering...),
+/// only the control command differs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum MessageSet {
+    /// `autoware_auto_control_msgs` / `autoware_auto_vehicle_msgs`, e.g. Autoware on galactic
+    #[default]
+    Auto,
+    /// `autoware_control_msgs` / `autoware_vehicle_msgs` of recent Autoware releases
+    New,
+}
+
+/// `autoware_control_msgs/msg/Lateral`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Lateral {
+    pub stamp: builtin_interfaces::Time,
+    pub control_time: builtin_interfaces::Time,
+    pub steering_tire_angle: f32,
+    pub steering_tire_rotation_rate: f32,
+    pub is_defined_steering_tire_rotation_rate: bool,
+}
+
+/// `autoware_control_msgs/msg/Longitudinal`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Longitudinal {
+    pub stamp: builtin_interfaces::Time,
+    pub control_time: builtin_interfaces::Time,
+    pub velocity: f32,
+    pub acceleration: f32,
+    pub jerk: f32,
+    pub is_defined_acceleration: bool,
+    pub is_defined_jerk: bool,
+}
+
+/// `autoware_control_msgs/msg/Control`, successor of `AckermannControlCommand`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Control {
+    pub stamp: builtin_interfaces::Time,
+    pub control_time: builtin_interfaces::Time,
+    pub lateral: Lateral,
+    pub longitudinal: Longitudinal,
+}
+
+impl From<&AckermannControlCommand> for Control {
+    fn from(command: &AckermannControlCommand) -> Self {
+        Control {
+            stamp: command.stamp.clone(),
+            control_time: command.stamp.clone(),
+            lateral: Lateral {
+                stamp: command.lateral.stamp.clone(),
+                control_time: command.lateral.stamp.clone(),
+                steering_tire_angle: command.lateral.steering_tire_angle,
+                steering_tire_rotation_rate: command.lateral.steering_tire_rotation_rate,
+                is_defined_steering_tire_rotation_rate: false,
+            },
+            longitudinal: Longitudinal {
+                stamp: command.longitudinal.stamp.clone(),
+                control_time: command.longitudinal.stamp.clone(),
+                velocity: command.longitudinal.speed,
+                acceleration: command.longitudinal.acceleration,
+                jerk: command.longitudinal.jerk,
+                is_defined_acceleration: true,
+                is_defined_jerk: false,
+            },
+        }
+    }
+}
+
+impl From<Control> for AckermannControlCommand {
+    fn from(control: Control) -> Self {
+        AckermannControlCommand {
+            stamp: control.stamp,
+            lateral: AckermannLateralCommand {
+                stamp: control.lateral.stamp,
+                steering_tire_angle: control.lateral.steering_tire_angle,
+                steering_tire_rotation_rate: control.lateral.steering_tire_rotation_rate,
+            },
+            longitudinal: LongitudinalCommand {
+                stamp: control.longitudinal.stamp,
+                speed: control.longitudinal.velocity,
+                acceleration: control.longitudinal.acceleration,
+                jerk: control.longitudinal.jerk,
+            },
+        }
+    }
+}
+
+/// CDR-encode a control command in the layout of `set`.
+pub fn encode_control_command(set: MessageSet, command: &AckermannControlCommand) -> Vec<u8> {
+    match set {
+        MessageSet::Auto => cdr::serialize::<_, _, CdrLe>(command, Infinite),
+        MessageSet::New => cdr::serialize::<_, _, CdrLe>(&Control::from(command), Infinite),
+    }
+    .unwrap()
+}
+
+/// Decode a control command encoded in the layout of `set`.
+pub fn decode_control_command(set: MessageSet, payload: &[u8]) -> Option<AckermannControlCommand> {
+    match set {
+        MessageSet::Auto => cdr::deserialize_from(payload, cdr::size::Infinite).ok(),
+        MessageSet::New => cdr::deserialize_from::<_, Control, _>(payload, cdr::size::Infinite)
+            .ok()
+            .map(AckermannControlCommand::from),
+    }
+}
+
+/// Publish a control command in the layout of `set`, logging instead of failing.
+pub(crate) fn publish_control_command(
+    transport: &dyn Transport,
+    key: &str,
+    set: MessageSet,
+    command: &AckermannControlCommand,
+) {
+    if let Err(err) = transport.publish(key, encode_control_command(set, command)) {
+        log::error!("Failed to publish on {}: {:?}\r", key, err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_loop::new_control_command;
+
+    #[test]
+    fn new_layout_round_trip() {
+        let command = new_control_command(-2.0, 0.5, 0.1);
+        let auto = encode_control_command(MessageSet::Auto, &command);
+        let new = encode_control_command(MessageSet::New, &command);
+        assert_ne!(auto.len(), new.len());
+        let decoded = decode_control_command(MessageSet::New, &new).unwrap();
+        assert_eq!(decoded.longitudinal.speed, -2.0);
+        assert_eq!(decoded.longitudinal.acceleration, 0.5);
+        assert_eq!(decoded.lateral.steering_tire_angle, 0.1);
+    }
+}
@@ -0,0 +1,145 @@
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use zenoh::key_expr::keyexpr;
+
+use crate::transport::{Callback, QueryHandler, Subscription, Transport};
+
+type Subscribers = Mutex<HashMap<u64, (String, Arc<Callback>)>>;
+type Queryables = Mutex<HashMap<u64, (String, Arc<QueryHandler>)>>;
+
+fn matches(a: &str, b: &str) -> bool {
+    match (keyexpr::new(a), keyexpr::new(b)) {
+        (Ok(a), Ok(b)) => a.intersects(b),
+        _ => a == b,
+    }
+}
+
+struct MockSubscription<T> {
+    id: u64,
+    entries: Weak<Mutex<HashMap<u64, T>>>,
+}
+
+impl<T> Drop for MockSubscription<T> {
+    fn drop(&mut self) {
+        if let Some(entries) = self.entries.upgrade() {
+            entries.lock().unwrap().remove(&self.id);
+        }
+    }
+}
+
+/// In-process `Transport` for tests: every publication is recorded and delivered to the local
+/// subscribers, and queries are answered by the local queryables.
+#[derive(Default)]
+pub struct MockTransport {
+    published: Mutex<Vec<(String, Vec<u8>)>>,
+    subscribers: Arc<Subscribers>,
+    next_id: Mutex<u64>,
+    queryables: Arc<Queryables>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver `payload` to the subscribers of `key` as if it came from the network.
+    pub fn inject(&self, key: &str, payload: Vec<u8>) {
+        let callbacks: Vec<Arc<Callback>> = self
+            .subscribers
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(sub_key, _)| matches(sub_key, key))
+            .map(|(_, callback)| callback.clone())
+            .collect();
+        for callback in callbacks {
+            callback(&payload);
+        }
+    }
+
+    /// CDR-encode `msg` and `inject` it.
+    pub fn inject_msg<T: serde::Serialize>(&self, key: &str, msg: &T) {
+        self.inject(
+            key,
+            cdr::serialize::<_, _, cdr::CdrLe>(msg, cdr::Infinite).unwrap(),
+        );
+    }
+
+    fn next_id(&self) -> u64 {
+        let mut next_id = self.next_id.lock().unwrap();
+        *next_id += 1;
+        *next_id
+    }
+
+    /// Payloads published on `key`, oldest first
+    pub fn published(&self, key: &str) -> Vec<Vec<u8>> {
+        self.published
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(pub_key, _)| pub_key == key)
+            .map(|(_, payload)| payload.clone())
+            .collect()
+    }
+
+    /// Latest message published on `key`, decoded as `T`
+    pub fn last_published<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let payload = self.published(key).pop()?;
+        cdr::deserialize_from::<_, T, _>(&*payload, cdr::size::Infinite).ok()
+    }
+
+    pub fn clear_published(&self) {
+        self.published.lock().unwrap().clear();
+    }
+}
+
+impl Transport for MockTransport {
+    fn publish(&self, key: &str, payload: Vec<u8>) -> zenoh::Result<()> {
+        self.published
+            .lock()
+            .unwrap()
+            .push((key.to_owned(), payload.clone()));
+        self.inject(key, payload);
+        Ok(())
+    }
+
+    fn subscribe(&self, key: &str, callback: Callback) -> zenoh::Result<Subscription> {
+        let id = self.next_id();
+        self.subscribers
+            .lock()
+            .unwrap()
+            .insert(id, (key.to_owned(), Arc::new(callback)));
+        Ok(Subscription::new(MockSubscription {
+            id,
+            entries: Arc::downgrade(&self.subscribers),
+        }))
+    }
+
+    fn query(&self, key: &str, payload: Vec<u8>, _timeout: Duration) -> zenoh::Result<Vec<u8>> {
+        let handler = self
+            .queryables
+            .lock()
+            .unwrap()
+            .values()
+            .find(|(query_key, _)| matches(query_key, key))
+            .map(|(_, handler)| handler.clone());
+        match handler {
+            Some(handler) => Ok(handler(&payload)),
+            None => Err(format!("No queryable on {}", key).into()),
+        }
+    }
+
+    fn serve(&self, key: &str, handler: QueryHandler) -> zenoh::Result<Subscription> {
+        let id = self.next_id();
+        self.queryables
+            .lock()
+            .unwrap()
+            .insert(id, (key.to_owned(), Arc::new(handler)));
+        Ok(Subscription::new(MockSubscription {
+            id,
+            entries: Arc::downgrade(&self.queryables),
+        }))
+    }
+}
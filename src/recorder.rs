@@ -0,0 +1,562 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::external::CommandSource;
+use crate::mcap::McapWriter;
+use crate::messages::MessageSet;
+use crate::topics::{ServiceKey, Topics};
+use crate::transport::{Callback, QueryHandler, Subscription, Transport};
+
+pub(crate) const ENGAGE_TOPIC: &str = "/api/autoware/set/engage";
+
+/// Message encoding of the channels without a ros2msg schema (service payloads, unknown keys).
+/// The "ros2" profile requires a schema for "cdr" channels, so ROS 2 tools skip these instead.
+const RAW_ENCODING: &str = "x-zenoh-cdr";
+
+const SEPARATOR: &str =
+    "================================================================================\n";
+
+/// ROS 2 message definitions (`ros2msg`) of the recorded topics
+mod definition {
+    pub const GATE_MODE: &str = "uint8 AUTO = 0\nuint8 EXTERNAL = 1\n\nuint8 data\n";
+    pub const ENGAGE: &str = "builtin_interfaces/Time stamp\nbool enable\n";
+    pub const CONTROL_MODE_REPORT: &str = "uint8 NO_COMMAND = 0
+uint8 AUTONOMOUS = 1
+uint8 AUTONOMOUS_STEER_ONLY = 2
+uint8 AUTONOMOUS_VELOCITY_ONLY = 3
+uint8 MANUAL = 4
+uint8 DISENGAGED = 5
+uint8 NOT_READY = 6
+
+builtin_interfaces/Time stamp
+uint8 mode
+";
+    pub const GEAR_COMMAND: &str = "builtin_interfaces/Time stamp\nuint8 command\n";
+    pub const GEAR_REPORT: &str = "builtin_interfaces/Time stamp\nuint8 report\n";
+    pub const STEERING_REPORT: &str =
+        "builtin_interfaces/Time stamp\nfloat32 steering_tire_angle\n";
+    pub const CONTROL_COMMAND: &str = "builtin_interfaces/Time stamp
+autoware_auto_control_msgs/AckermannLateralCommand lateral
+autoware_auto_control_msgs/LongitudinalCommand longitudinal
+";
+    pub const LATERAL_COMMAND: &str = "MSG: autoware_auto_control_msgs/AckermannLateralCommand
+builtin_interfaces/Time stamp
+float32 steering_tire_angle
+float32 steering_tire_rotation_rate
+";
+    pub const LONGITUDINAL_COMMAND: &str = "MSG: autoware_auto_control_msgs/LongitudinalCommand
+builtin_interfaces/Time stamp
+float32 speed
+float32 acceleration
+float32 jerk
+";
+    pub const CONTROL: &str = "builtin_interfaces/Time stamp
+builtin_interfaces/Time control_time
+autoware_control_msgs/Lateral lateral
+autoware_control_msgs/Longitudinal longitudinal
+";
+    pub const LATERAL: &str = "MSG: autoware_control_msgs/Lateral
+builtin_interfaces/Time stamp
+builtin_interfaces/Time control_time
+float32 steering_tire_angle
+float32 steering_tire_rotation_rate
+bool is_defined_steering_tire_rotation_rate
+";
+    pub const LONGITUDINAL: &str = "MSG: autoware_control_msgs/Longitudinal
+builtin_interfaces/Time stamp
+builtin_interfaces/Time control_time
+float32 velocity
+float32 acceleration
+float32 jerk
+bool is_defined_acceleration
+bool is_defined_jerk
+";
+    pub const VELOCITY_REPORT: &str = "std_msgs/Header header
+float32 longitudinal_velocity
+float32 lateral_velocity
+float32 heading_rate
+";
+    pub const OPERATION_MODE_STATE: &str = "uint8 UNKNOWN = 0
+uint8 STOP = 1
+uint8 AUTONOMOUS = 2
+uint8 LOCAL = 3
+uint8 REMOTE = 4
+
+builtin_interfaces/Time stamp
+uint8 mode
+bool is_autoware_control_enabled
+bool is_in_transition
+bool is_stop_mode_available
+bool is_autonomous_mode_available
+bool is_local_mode_available
+bool is_remote_mode_available
+";
+    pub const ROUTE_STATE: &str = "uint16 UNKNOWN = 0
+uint16 UNSET = 1
+uint16 SET = 2
+uint16 ARRIVED = 3
+uint16 CHANGING = 4
+
+builtin_interfaces/Time stamp
+uint16 state
+";
+    pub const MRM_STATE: &str = "builtin_interfaces/Time stamp
+uint16 state
+uint16 UNKNOWN = 0
+uint16 NORMAL = 1
+uint16 MRM_OPERATING = 2
+uint16 MRM_SUCCEEDED = 3
+uint16 MRM_FAILED = 4
+
+uint16 behavior
+uint16 NONE = 1
+uint16 EMERGENCY_STOP = 2
+uint16 COMFORTABLE_STOP = 3
+";
+    pub const HAZARD_STATUS_STAMPED: &str = "builtin_interfaces/Time stamp
+autoware_auto_system_msgs/HazardStatus status
+";
+    pub const HAZARD_STATUS: &str = "MSG: autoware_auto_system_msgs/HazardStatus
+uint8 NO_FAULT = 0
+uint8 SAFE_FAULT = 1
+uint8 LATENT_FAULT = 2
+uint8 SINGLE_POINT_FAULT = 3
+
+uint8 level
+bool emergency
+bool emergency_holding
+diagnostic_msgs/DiagnosticStatus[] diag_no_fault
+diagnostic_msgs/DiagnosticStatus[] diag_safe_fault
+diagnostic_msgs/DiagnosticStatus[] diag_latent_fault
+diagnostic_msgs/DiagnosticStatus[] diag_single_point_fault
+";
+    pub const DIAGNOSTIC_STATUS: &str = "MSG: diagnostic_msgs/DiagnosticStatus
+byte OK=0
+byte WARN=1
+byte ERROR=2
+byte STALE=3
+
+byte level
+string name
+string message
+string hardware_id
+KeyValue[] values
+";
+    pub const KEY_VALUE: &str = "MSG: diagnostic_msgs/KeyValue
+string key
+string value
+";
+    pub const LOCALIZATION_INITIALIZATION_STATE: &str = "uint16 UNKNOWN = 0
+uint16 UNINITIALIZED = 1
+uint16 INITIALIZING = 2
+uint16 INITIALIZED = 3
+
+builtin_interfaces/Time stamp
+uint16 state
+";
+    pub const GEAR_SHIFT_STAMPED: &str = "builtin_interfaces/Time stamp
+tier4_external_api_msgs/GearShift gear_shift
+";
+    pub const GEAR_SHIFT: &str = "MSG: tier4_external_api_msgs/GearShift
+uint8 NONE = 0
+uint8 PARKING = 1
+uint8 REVERSE = 2
+uint8 NEUTRAL = 3
+uint8 DRIVE = 4
+uint8 LOW = 5
+
+uint8 data
+";
+    pub const EXTERNAL_CONTROL_STAMPED: &str = "builtin_interfaces/Time stamp
+tier4_external_api_msgs/ControlCommand control
+";
+    pub const EXTERNAL_CONTROL: &str = "MSG: tier4_external_api_msgs/ControlCommand
+float64 steering_angle
+float64 steering_angle_velocity
+float64 throttle
+float64 brake
+";
+    pub const HEARTBEAT: &str = "builtin_interfaces/Time stamp\n";
+    pub const HEADER: &str =
+        "MSG: std_msgs/Header\nbuiltin_interfaces/Time stamp\nstring frame_id\n";
+}
+
+/// How a Zenoh key shows up in the recording
+struct ChannelInfo {
+    topic: String,
+    /// ROS 2 type name and definition, or None for raw service payloads
+    schema: Option<(&'static str, String)>,
+    direction: &'static str,
+}
+
+fn known_channels(topics: &Topics) -> HashMap<String, ChannelInfo> {
+    let mut channels = HashMap::new();
+    let mut add = |key: &str, topic: &str, schema: Option<(&'static str, String)>, direction| {
+        let info = ChannelInfo {
+            topic: topic.to_owned(),
+            schema,
+            direction,
+        };
+        channels.insert(key.to_owned(), info);
+    };
+    use definition::*;
+    add(
+        &topics.gate_mode_cmd,
+        "/control/gate_mode_cmd",
+        Some(("tier4_control_msgs/msg/GateMode", GATE_MODE.to_owned())),
+        "command",
+    );
+    let external = format!("/external/{}/", topics.command_source.name());
+    let gear_command_type = "autoware_auto_vehicle_msgs/msg/GearCommand";
+    let (gear_schema, control_schema) = match (topics.command_source, topics.message_set) {
+        (CommandSource::Local | CommandSource::Remote, _) => {
+            add(
+                &topics.heartbeat,
+                &(external.clone() + "heartbeat"),
+                Some((
+                    "tier4_external_api_msgs/msg/Heartbeat",
+                    HEARTBEAT.to_owned(),
+                )),
+                "command",
+            );
+            (
+                (
+                    "tier4_external_api_msgs/msg/GearShiftStamped",
+                    [GEAR_SHIFT_STAMPED, GEAR_SHIFT].join(SEPARATOR),
+                ),
+                (
+                    "tier4_external_api_msgs/msg/ControlCommandStamped",
+                    [EXTERNAL_CONTROL_STAMPED, EXTERNAL_CONTROL].join(SEPARATOR),
+                ),
+            )
+        }
+        (CommandSource::Selected, MessageSet::Auto) => (
+            (gear_command_type, GEAR_COMMAND.to_owned()),
+            (
+                "autoware_auto_control_msgs/msg/AckermannControlCommand",
+                [CONTROL_COMMAND, LATERAL_COMMAND, LONGITUDINAL_COMMAND].join(SEPARATOR),
+            ),
+        ),
+        (CommandSource::Selected, MessageSet::New) => (
+            (gear_command_type, GEAR_COMMAND.to_owned()),
+            (
+                "autoware_control_msgs/msg/Control",
+                [CONTROL, LATERAL, LONGITUDINAL].join(SEPARATOR),
+            ),
+        ),
+    };
+    add(
+        &topics.gear_cmd,
+        &(external.clone()
+            + match topics.command_source {
+                CommandSource::Selected => "gear_cmd",
+                CommandSource::Local | CommandSource::Remote => "shift_cmd",
+            }),
+        Some(gear_schema),
+        "command",
+    );
+    add(
+        &topics.control_cmd,
+        &(external + "control_cmd"),
+        Some(control_schema),
+        "command",
+    );
+    add(
+        &topics.current_gate_mode,
+        "/control/current_gate_mode",
+        Some(("tier4_control_msgs/msg/GateMode", GATE_MODE.to_owned())),
+        "status",
+    );
+    add(
+        &topics.engage_status,
+        "/api/autoware/get/engage",
+        Some(("autoware_auto_vehicle_msgs/msg/Engage", ENGAGE.to_owned())),
+        "status",
+    );
+    add(
+        &topics.control_mode,
+        "/vehicle/status/control_mode",
+        Some((
+            "autoware_auto_vehicle_msgs/msg/ControlModeReport",
+            CONTROL_MODE_REPORT.to_owned(),
+        )),
+        "status",
+    );
+    add(
+        &topics.gear_status,
+        "/vehicle/status/gear_status",
+        Some((
+            "autoware_auto_vehicle_msgs/msg/GearReport",
+            GEAR_REPORT.to_owned(),
+        )),
+        "status",
+    );
+    add(
+        &topics.velocity_status,
+        "/vehicle/status/velocity_status",
+        Some((
+            "autoware_auto_vehicle_msgs/msg/VelocityReport",
+            [VELOCITY_REPORT, HEADER].join(SEPARATOR),
+        )),
+        "status",
+    );
+    add(
+        &topics.steering_status,
+        "/vehicle/status/steering_status",
+        Some((
+            "autoware_auto_vehicle_msgs/msg/SteeringReport",
+            STEERING_REPORT.to_owned(),
+        )),
+        "status",
+    );
+    add(
+        &topics.operation_mode_state,
+        "/api/operation_mode/state",
+        Some((
+            "autoware_adapi_v1_msgs/msg/OperationModeState",
+            OPERATION_MODE_STATE.to_owned(),
+        )),
+        "status",
+    );
+    add(
+        &topics.route_state,
+        "/api/routing/state",
+        Some((
+            "autoware_adapi_v1_msgs/msg/RouteState",
+            ROUTE_STATE.to_owned(),
+        )),
+        "status",
+    );
+    add(
+        &topics.localization_initialization_state,
+        "/api/localization/initialization_state",
+        Some((
+            "autoware_adapi_v1_msgs/msg/LocalizationInitializationState",
+            LOCALIZATION_INITIALIZATION_STATE.to_owned(),
+        )),
+        "status",
+    );
+    add(
+        &topics.mrm_state,
+        "/api/fail_safe/mrm_state",
+        Some(("autoware_adapi_v1_msgs/msg/MrmState", MRM_STATE.to_owned())),
+        "status",
+    );
+    add(
+        &topics.hazard_status,
+        "/system/emergency/hazard_status",
+        Some((
+            "autoware_auto_system_msgs/msg/HazardStatusStamped",
+            [
+                HAZARD_STATUS_STAMPED,
+                HAZARD_STATUS,
+                DIAGNOSTIC_STATUS,
+                KEY_VALUE,
+            ]
+            .join(SEPARATOR),
+        )),
+        "status",
+    );
+    let services = [
+        (&topics.engage, ENGAGE_TOPIC),
+        (&topics.change_to_stop, "/api/operation_mode/change_to_stop"),
+        (
+            &topics.change_to_autonomous,
+            "/api/operation_mode/change_to_autonomous",
+        ),
+        (
+            &topics.change_to_local,
+            "/api/operation_mode/change_to_local",
+        ),
+        (
+            &topics.change_to_remote,
+            "/api/operation_mode/change_to_remote",
+        ),
+        (
+            &topics.select_external_command,
+            "/control/external_cmd_selector/select_external_command",
+        ),
+        (&topics.set_route_points, "/api/routing/set_route_points"),
+        (&topics.clear_route, "/api/routing/clear_route"),
+        (
+            &topics.initialize_localization,
+            "/api/localization/initialize",
+        ),
+    ];
+    for (service, name) in services {
+        match service {
+            ServiceKey::Query(key) => {
+                add(key, name, None, "request");
+                add(&(key.to_owned() + "/reply"), name, None, "reply");
+            }
+            ServiceKey::Topic { request, reply } => {
+                add(request, name, None, "request");
+                add(reply, name, None, "reply");
+            }
+        }
+    }
+    channels
+}
+
+/// Zenoh key on which to publish the commands recorded on the ROS 2 `topic`
+pub(crate) fn key_of(topics: &Topics, topic: &str) -> Option<String> {
+    known_channels(topics)
+        .into_iter()
+        .find(|(_, info)| info.topic == topic && info.direction == "command")
+        .map(|(key, _)| key)
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+struct Channel {
+    id: u16,
+    sequence: u32,
+}
+
+struct RecorderState {
+    writer: Option<McapWriter<BufWriter<File>>>,
+    schemas: HashMap<&'static str, u16>,
+    channels: HashMap<String, Channel>,
+}
+
+/// Write every sample going through a `RecordingTransport` to an MCAP file, with CDR payloads
+/// and one channel per Zenoh key. The key is kept in the `zenoh_key` channel metadata.
+pub struct Recorder {
+    known: HashMap<String, ChannelInfo>,
+    state: Mutex<RecorderState>,
+}
+
+impl Recorder {
+    /// `topics` gives the ROS 2 names and types of the keys used by the controller.
+    pub fn create(path: impl AsRef<Path>, topics: &Topics) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let writer = McapWriter::new(file, "ros2", env!("CARGO_PKG_NAME"))?;
+        Ok(Recorder {
+            known: known_channels(topics),
+            state: Mutex::new(RecorderState {
+                writer: Some(writer),
+                schemas: HashMap::new(),
+                channels: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Record `payload` as seen on `key` now.
+    pub fn record(&self, key: &str, payload: &[u8]) {
+        if let Err(err) = self.try_record(key, payload) {
+            log::error!("Failed to record {}: {:?}\r", key, err);
+        }
+    }
+
+    fn try_record(&self, key: &str, payload: &[u8]) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let RecorderState {
+            writer,
+            schemas,
+            channels,
+        } = &mut *state;
+        let writer = match writer {
+            Some(writer) => writer,
+            None => return Ok(()), // finished
+        };
+        if !channels.contains_key(key) {
+            let info = self.known.get(key);
+            let (schema_id, encoding) = match info.and_then(|info| info.schema.as_ref()) {
+                Some((name, data)) => match schemas.get(name) {
+                    Some(id) => (*id, "cdr"),
+                    None => {
+                        let id = writer.add_schema(name, "ros2msg", data.as_bytes())?;
+                        schemas.insert(name, id);
+                        (id, "cdr")
+                    }
+                },
+                None => (0, RAW_ENCODING),
+            };
+            let topic = match info {
+                Some(info) => info.topic.clone(),
+                None => "/".to_owned() + key,
+            };
+            let direction = info.map(|info| info.direction).unwrap_or("unknown");
+            let id = writer.add_channel(
+                schema_id,
+                &topic,
+                encoding,
+                &[("zenoh_key", key), ("direction", direction)],
+            )?;
+            channels.insert(key.to_owned(), Channel { id, sequence: 0 });
+        }
+        let channel = channels.get_mut(key).unwrap();
+        let time = now_nanos();
+        writer.write_message(channel.id, channel.sequence, time, time, payload)?;
+        channel.sequence = channel.sequence.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Complete the file. Later samples are dropped.
+    pub fn finish(&self) -> io::Result<()> {
+        match self.state.lock().unwrap().writer.take() {
+            Some(writer) => writer.finish().map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            log::error!("Failed to complete the recording: {:?}\r", err);
+        }
+    }
+}
+
+/// `Transport` that records everything published and received through `inner`.
+/// Query replies are recorded on `<key>/reply`.
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Arc<dyn Transport>, recorder: Arc<Recorder>) -> Self {
+        RecordingTransport { inner, recorder }
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn publish(&self, key: &str, payload: Vec<u8>) -> zenoh::Result<()> {
+        self.recorder.record(key, &payload);
+        self.inner.publish(key, payload)
+    }
+
+    fn subscribe(&self, key: &str, callback: Callback) -> zenoh::Result<Subscription> {
+        let recorder = self.recorder.clone();
+        let sub_key = key.to_owned();
+        self.inner.subscribe(
+            key,
+            Box::new(move |payload| {
+                recorder.record(&sub_key, payload);
+                callback(payload);
+            }),
+        )
+    }
+
+    fn query(&self, key: &str, payload: Vec<u8>, timeout: Duration) -> zenoh::Result<Vec<u8>> {
+        self.recorder.record(key, &payload);
+        let reply = self.inner.query(key, payload, timeout)?;
+        self.recorder.record(&(key.to_owned() + "/reply"), &reply);
+        Ok(reply)
+    }
+
+    fn serve(&self, key: &str, handler: QueryHandler) -> zenoh::Result<Subscription> {
+        self.inner.serve(key, handler)
+    }
+}
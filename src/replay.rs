@@ -0,0 +1,408 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use zenoh_ros_type::autoware_auto_control_msgs::AckermannControlCommand;
+
+use crate::control_loop::{new_control_command, ControlLoop, CONTROL_PERIOD, ZERO_BURST_CYCLES};
+use crate::manual_control::send_engage;
+use crate::mcap::McapReader;
+use crate::messages::{decode_control_command, publish_control_command};
+use crate::recorder::{key_of, ENGAGE_TOPIC};
+use crate::settings::Settings;
+use crate::status::{LinkHealth, StatusTracker, LINK_TIMEOUT};
+use crate::topics::Topics;
+use crate::transport::Transport;
+
+/// How long the vehicle may stay faster than the replayed command before aborting
+const OVERSPEED_TIMEOUT: Duration = Duration::from_secs(2);
+/// Acceleration (m/s^2) of the stop command sent on pause, abort and at the end
+const STOP_ACCELERATION: f32 = -1.0;
+
+/// A command read back from a recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedCommand {
+    /// ROS 2 name of the topic or service, e.g. `/control/gate_mode_cmd`
+    pub topic: String,
+    /// `command` for topics, `request` for service calls
+    pub direction: String,
+    /// Time since the first command of the recording
+    pub offset: Duration,
+    pub payload: Vec<u8>,
+}
+
+/// The commands of a session recorded by `Recorder`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    pub commands: Vec<RecordedCommand>,
+}
+
+impl Recording {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    pub fn parse(file: &[u8]) -> io::Result<Self> {
+        let reader = McapReader::parse(file)?;
+        let mut commands = Vec::new();
+        let mut start = None;
+        for message in reader.messages {
+            let channel = match reader.channels.get(&message.channel_id) {
+                Some(channel) => channel,
+                None => continue,
+            };
+            let direction = channel.metadata.get("direction").map(String::as_str);
+            if !matches!(direction, Some("command") | Some("request")) {
+                continue;
+            }
+            let start = *start.get_or_insert(message.log_time);
+            commands.push(RecordedCommand {
+                topic: channel.topic.clone(),
+                direction: direction.unwrap().to_owned(),
+                offset: Duration::from_nanos(message.log_time.saturating_sub(start)),
+                payload: message.data,
+            });
+        }
+        Ok(Recording { commands })
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.commands
+            .last()
+            .map(|command| command.offset)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayOptions {
+    /// Playback speed, 1.0 keeps the original timing
+    pub speed: f32,
+    /// Abort when the vehicle goes this much faster (m/s) than the speed limit, or stays this
+    /// much faster than the replayed command
+    pub max_velocity_error: f32,
+    /// Speed and steering limits the replayed commands are clamped to
+    pub settings: Settings,
+    /// Runtime cap (m/s) on top of the speed limits, e.g. `ManualController::speed_cap`
+    pub speed_cap: Option<f32>,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        ReplayOptions {
+            speed: 1.0,
+            max_velocity_error: 2.0,
+            settings: Settings::default(),
+            speed_cap: None,
+        }
+    }
+}
+
+/// Accept a playback speed only if it is positive and finite.
+pub fn check_speed(speed: f32) -> Result<f32, String> {
+    if speed > 0.0 && speed.is_finite() {
+        Ok(speed)
+    } else {
+        Err(format!(
+            "Invalid playback speed {}: must be positive",
+            speed
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayState {
+    Running,
+    Paused,
+    Finished,
+    /// Stopped by `abort` or a failed safety check, with the reason
+    Aborted(String),
+}
+
+struct Context {
+    transport: Arc<dyn Transport>,
+    topics: Topics,
+    status: Arc<StatusTracker>,
+    options: ReplayOptions,
+    paused: Arc<AtomicBool>,
+    abort: Arc<AtomicBool>,
+    state: Arc<Mutex<ReplayState>>,
+    guid: i64,
+    // recorded samples that could not be replayed, and the topics already warned about
+    skipped: Arc<AtomicUsize>,
+    skipped_topics: HashSet<String>,
+    // latest replayed control command
+    speed: f32,
+    steering_tire_angle: f32,
+    overspeed_since: Option<Instant>,
+}
+
+impl Context {
+    fn publish_stop(&self, cycles: u32) {
+        let command = new_control_command(0.0, STOP_ACCELERATION, self.steering_tire_angle);
+        for _ in 0..cycles {
+            publish_control_command(
+                self.transport.as_ref(),
+                &self.topics.control_cmd,
+                self.topics.message_set,
+                &command,
+            );
+            thread::sleep(CONTROL_PERIOD);
+        }
+    }
+
+    /// Compare the telemetry with what is being replayed.
+    fn check(&mut self) -> Result<(), String> {
+        self.status.check_link();
+        let status = self.status.status();
+        if status.link != LinkHealth::Alive {
+            return Err("No vehicle telemetry".to_owned());
+        }
+        let velocity = status.current_velocity.abs();
+        let limit = ControlLoop::speed_limit(
+            &self.options.settings.control,
+            status.gear,
+            self.options.speed_cap,
+        );
+        if velocity > limit + self.options.max_velocity_error {
+            return Err(format!(
+                "Velocity {:.2} m/s above the limit {:.2} m/s",
+                velocity, limit
+            ));
+        }
+        if velocity > self.speed.abs() + self.options.max_velocity_error {
+            let since = *self.overspeed_since.get_or_insert_with(Instant::now);
+            if since.elapsed() > OVERSPEED_TIMEOUT {
+                return Err(format!(
+                    "Velocity {:.2} m/s does not follow the command {:.2} m/s",
+                    velocity, self.speed
+                ));
+            }
+        } else {
+            self.overspeed_since = None;
+        }
+        Ok(())
+    }
+
+    /// Count a sample that cannot be replayed, warning once per topic.
+    fn skip(&mut self, command: &RecordedCommand, reason: &str) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+        if self.skipped_topics.insert(command.topic.clone()) {
+            log::warn!("Skip {}: {}\r", command.topic, reason);
+        }
+    }
+
+    fn send(&mut self, command: &RecordedCommand, seq: u64) {
+        if command.direction == "request" {
+            if command.topic == ENGAGE_TOPIC {
+                let _ = send_engage(self.transport.as_ref(), &self.topics.engage, self.guid, seq);
+            } else {
+                self.skip(command, "only the engage service is replayed");
+            }
+            return;
+        }
+        let key = match key_of(&self.topics, &command.topic) {
+            Some(key) => key,
+            None => {
+                self.skip(command, "not a command of the current command source");
+                return;
+            }
+        };
+        if key == self.topics.control_cmd {
+            let mut control =
+                match decode_control_command(self.topics.message_set, &command.payload) {
+                    Some(control) => control,
+                    None => {
+                        self.skip(command, "not an external/selected control command");
+                        return;
+                    }
+                };
+            self.limit(&mut control);
+            self.speed = control.longitudinal.speed;
+            self.steering_tire_angle = control.lateral.steering_tire_angle;
+            publish_control_command(
+                self.transport.as_ref(),
+                &key,
+                self.topics.message_set,
+                &control,
+            );
+            return;
+        }
+        if let Err(err) = self.transport.publish(&key, command.payload.clone()) {
+            log::error!("Failed to replay {}: {:?}\r", command.topic, err);
+        }
+    }
+
+    /// Clamp a replayed command to the limits, as the control loop does with any input source.
+    fn limit(&self, control: &mut AckermannControlCommand) {
+        let status = self.status.status();
+        let settings = &self.options.settings;
+        let limit =
+            ControlLoop::speed_limit(&settings.control, status.gear, self.options.speed_cap);
+        let speed = num::clamp(control.longitudinal.speed, -limit, limit);
+        let steering_limit =
+            ControlLoop::steering_limit(settings, status.current_velocity.abs().max(speed.abs()));
+        control.longitudinal.speed = speed;
+        control.lateral.steering_tire_angle = num::clamp(
+            control.lateral.steering_tire_angle,
+            -steering_limit,
+            steering_limit,
+        );
+    }
+
+    fn set_state(&self, state: ReplayState) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    fn run(&mut self, recording: &Recording) -> Result<(), String> {
+        // Give the first status samples a chance to arrive
+        let deadline = Instant::now() + LINK_TIMEOUT;
+        while self.status.status().link == LinkHealth::Unknown && Instant::now() < deadline {
+            thread::sleep(CONTROL_PERIOD);
+        }
+        let mut start = Instant::now();
+        for (seq, command) in recording.commands.iter().enumerate() {
+            let due = command.offset.div_f32(self.options.speed);
+            loop {
+                if self.abort.load(Ordering::Relaxed) {
+                    return Err("Aborted by the operator".to_owned());
+                }
+                self.check()?;
+                if self.paused.load(Ordering::Relaxed) {
+                    // Hold the vehicle, and shift the timeline by the pause
+                    self.set_state(ReplayState::Paused);
+                    let pause = Instant::now();
+                    self.publish_stop(1);
+                    start += pause.elapsed();
+                    continue;
+                }
+                self.set_state(ReplayState::Running);
+                let elapsed = start.elapsed();
+                if elapsed >= due {
+                    break;
+                }
+                thread::sleep((due - elapsed).min(CONTROL_PERIOD));
+            }
+            self.send(command, seq as u64);
+        }
+        Ok(())
+    }
+}
+
+/// Republish the commands of a `Recording` with their original timing, scaled by
+/// `ReplayOptions::speed`, while checking the vehicle telemetry.
+/// The vehicle is stopped on pause, on abort, and at the end of the recording.
+pub struct Replayer {
+    paused: Arc<AtomicBool>,
+    skipped: Arc<AtomicUsize>,
+    abort: Arc<AtomicBool>,
+    state: Arc<Mutex<ReplayState>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Replayer {
+    /// `status` must be fed by the vehicle, e.g. `ManualController::status_tracker`.
+    /// Fail if the options are invalid.
+    pub fn start(
+        recording: Recording,
+        transport: Arc<dyn Transport>,
+        topics: Topics,
+        status: Arc<StatusTracker>,
+        options: ReplayOptions,
+    ) -> Result<Self, String> {
+        check_speed(options.speed)?;
+        let paused = Arc::new(AtomicBool::new(false));
+        let abort = Arc::new(AtomicBool::new(false));
+        let state = Arc::new(Mutex::new(ReplayState::Running));
+        let skipped = Arc::new(AtomicUsize::new(0));
+        let mut context = Context {
+            transport,
+            topics,
+            status,
+            options,
+            paused: paused.clone(),
+            abort: abort.clone(),
+            state: state.clone(),
+            guid: rand::random::<i64>(),
+            skipped: skipped.clone(),
+            skipped_topics: HashSet::new(),
+            speed: 0.0,
+            steering_tire_angle: 0.0,
+            overspeed_since: None,
+        };
+        let thread = thread::spawn(move || {
+            let result = context.run(&recording);
+            context.publish_stop(ZERO_BURST_CYCLES);
+            match result {
+                Ok(()) => {
+                    log::info!("Replay finished\r");
+                    context.set_state(ReplayState::Finished);
+                }
+                Err(reason) => {
+                    log::warn!("Replay aborted: {}\r", reason);
+                    context.set_state(ReplayState::Aborted(reason));
+                }
+            }
+        });
+        Ok(Replayer {
+            paused,
+            skipped,
+            abort,
+            state,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Stop replaying and bring the vehicle to a stop.
+    pub fn abort(&self) {
+        self.abort.store(true, Ordering::Relaxed);
+    }
+
+    /// Number of recorded samples that could not be replayed so far: service calls other
+    /// than engage, and commands of another command source or message set
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    pub fn state(&self) -> ReplayState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Whether the replay finished or was aborted
+    pub fn is_done(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_none_or(|thread| thread.is_finished())
+    }
+
+    /// Block until the replay is done.
+    pub fn wait(&mut self) -> ReplayState {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.state()
+    }
+}
+
+impl Drop for Replayer {
+    fn drop(&mut self) {
+        self.abort();
+        self.wait();
+    }
+}
@@ -0,0 +1,114 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use zenoh_ros_type::{builtin_interfaces, geometry_msgs, std_msgs};
+
+use crate::settings::MapPose;
+
+/// `autoware_adapi_v1_msgs/msg/RouteState` constants
+pub mod route_state {
+    pub const UNKNOWN: u16 = 0;
+    pub const UNSET: u16 = 1;
+    pub const SET: u16 = 2;
+    pub const ARRIVED: u16 = 3;
+    pub const CHANGING: u16 = 4;
+}
+
+pub fn route_state_name(state: u16) -> &'static str {
+    match state {
+        route_state::UNSET => "Unset",
+        route_state::SET => "Set",
+        route_state::ARRIVED => "Arrived",
+        route_state::CHANGING => "Changing",
+        _ => "Unknown",
+    }
+}
+
+/// `autoware_adapi_v1_msgs/msg/RouteState`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RouteState {
+    pub stamp: builtin_interfaces::Time,
+    pub state: u16,
+}
+
+/// `autoware_adapi_v1_msgs/msg/RouteOption`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteOption {
+    pub allow_goal_modification: bool,
+}
+
+/// `autoware_adapi_v1_msgs/srv/SetRoutePoints` request
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SetRoutePointsRequest {
+    pub header: std_msgs::Header,
+    pub option: RouteOption,
+    pub goal: geometry_msgs::Pose,
+    pub waypoints: Vec<geometry_msgs::Pose>,
+}
+
+/// Read a pose typed by the operator: the name of a preset, or `x y [yaw]`
+/// (m, m, deg) separated by spaces or commas.
+pub fn parse_pose(input: &str, presets: &BTreeMap<String, MapPose>) -> Result<MapPose, String> {
+    let input = input.trim();
+    if let Some(pose) = presets.get(input) {
+        return Ok(*pose);
+    }
+    let values = input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(str::parse::<f64>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("Neither a preset nor coordinates: {}", input))?;
+    match values[..] {
+        [x, y] => Ok(MapPose { x, y, yaw: 0.0 }),
+        [x, y, yaw] => Ok(MapPose { x, y, yaw }),
+        _ => Err(format!("Expected x y [yaw], got {}", input)),
+    }
+}
+
+/// The pose in ROS 2 terms, with the yaw as a quaternion
+pub fn ros_pose(pose: &MapPose) -> geometry_msgs::Pose {
+    let half_yaw = pose.yaw.to_radians() / 2.0;
+    geometry_msgs::Pose {
+        position: geometry_msgs::Point {
+            x: pose.x,
+            y: pose.y,
+            z: 0.0,
+        },
+        orientation: geometry_msgs::Quaternion {
+            x: 0.0,
+            y: 0.0,
+            z: half_yaw.sin(),
+            w: half_yaw.cos(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_presets_and_coordinates() {
+        let presets = BTreeMap::from([(
+            "parking".to_owned(),
+            MapPose {
+                x: 1.0,
+                y: 2.0,
+                yaw: 90.0,
+            },
+        )]);
+        assert_eq!(parse_pose(" parking ", &presets).unwrap().yaw, 90.0);
+        assert_eq!(
+            parse_pose("3.5, -4 180", &presets).unwrap(),
+            MapPose {
+                x: 3.5,
+                y: -4.0,
+                yaw: 180.0
+            }
+        );
+        assert!(parse_pose("3.5", &presets).is_err());
+        assert!(parse_pose("garage", &presets).is_err());
+        let pose = ros_pose(&presets["parking"]);
+        assert!((pose.orientation.z - pose.orientation.w).abs() < 1e-9);
+    }
+}
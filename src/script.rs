@@ -0,0 +1,302 @@
+use serde_derive::Deserialize;
+use std::error::Error;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use zenoh_ros_type::{
+    autoware_auto_vehicle_msgs::gear_command, tier4_control_msgs::gate_mode_data,
+};
+
+use crate::control_loop::CONTROL_PERIOD;
+use crate::manual_control::ManualController;
+use crate::status::ControllerStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gear {
+    Drive,
+    Reverse,
+    Park,
+    Neutral,
+}
+
+impl Gear {
+    fn command(self) -> u8 {
+        match self {
+            Gear::Drive => gear_command::DRIVE,
+            Gear::Reverse => gear_command::REVERSE,
+            Gear::Park => gear_command::PARK,
+            Gear::Neutral => gear_command::NEUTRAL,
+        }
+    }
+}
+
+/// One step of a maneuver. Speeds are in km/h, angles in degrees and durations in seconds.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Switch to EXTERNAL and wait until Autoware is engaged
+    Engage,
+    /// Switch back to AUTO
+    Disengage,
+    /// Send the gear command and wait until it is reported
+    Gear(Gear),
+    /// Ramp the target speed linearly, then wait until the vehicle reaches it
+    Speed {
+        target: f32,
+        #[serde(default)]
+        over: f32,
+    },
+    /// Set the steering angle and keep everything as is for `hold` seconds
+    Steer {
+        angle: f32,
+        #[serde(default)]
+        hold: f32,
+    },
+    /// Keep the current targets for some seconds
+    Hold(f32),
+    /// Set the target speed to 0 and wait until the vehicle stands still
+    Stop,
+    /// Stop, then shift to PARK
+    Park,
+}
+
+fn default_tolerance() -> f32 {
+    1.0
+}
+
+fn default_timeout() -> f32 {
+    30.0
+}
+
+/// A maneuver loaded from a YAML or JSON file, e.g.
+///
+/// ```yaml
+/// steps:
+///   - engage
+///   - gear: drive
+///   - speed: { target: 20, over: 5 }
+///   - steer: { angle: 10, hold: 3 }
+///   - stop
+///   - park
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Script {
+    // `gear: drive` rather than YAML's default `!gear drive`
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    pub steps: Vec<Step>,
+    /// How close (km/h) the velocity must get to the target to finish a `speed` or `stop` step
+    #[serde(default = "default_tolerance")]
+    pub tolerance: f32,
+    /// Longest wait (s) for the vehicle to follow a step
+    #[serde(default = "default_timeout")]
+    pub timeout: f32,
+}
+
+fn kmh_to_mps(speed: f32) -> f32 {
+    speed * 1000.0 / 3600.0
+}
+
+impl Script {
+    /// Files ending in `.yaml` or `.yml` are read as YAML, others as JSON (JSON5 accepted).
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => Self::from_yaml(&content),
+            _ => Self::from_json(&content),
+        }
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(serde_yaml::from_str(content)?)
+    }
+
+    pub fn from_json(content: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(json5::from_str(content)?)
+    }
+
+    /// Execute the steps through `controller`, whose control loop must be running.
+    /// Set `abort` to stop early. The vehicle is brought to a stop on abort or failure.
+    pub fn run(&self, controller: &ManualController, abort: &AtomicBool) -> Result<(), String> {
+        let runner = Runner {
+            script: self,
+            controller,
+            abort,
+        };
+        let result = self.steps.iter().enumerate().try_for_each(|(i, step)| {
+            log::info!("Step {}/{}: {:?}\r", i + 1, self.steps.len(), step);
+            runner.step(step)
+        });
+        if result.is_err() {
+            controller.update_control_command(0.0, controller.target_steering_tire_angle());
+        }
+        result
+    }
+}
+
+struct Runner<'a> {
+    script: &'a Script,
+    controller: &'a ManualController,
+    abort: &'a AtomicBool,
+}
+
+impl Runner<'_> {
+    fn check_abort(&self) -> Result<(), String> {
+        if self.abort.load(Ordering::Relaxed) {
+            Err("Aborted by the operator".to_owned())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn sleep(&self, duration: Duration) -> Result<(), String> {
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            self.check_abort()?;
+            thread::sleep((duration - start.elapsed()).min(CONTROL_PERIOD));
+        }
+        Ok(())
+    }
+
+    /// Wait until the reported status satisfies `done`, for at most `Script::timeout`.
+    fn wait_for(&self, what: &str, done: impl Fn(&ControllerStatus) -> bool) -> Result<(), String> {
+        let timeout = Duration::from_secs_f32(self.script.timeout);
+        let start = Instant::now();
+        while !done(&self.controller.status()) {
+            self.check_abort()?;
+            if start.elapsed() > timeout {
+                return Err(format!("Timeout waiting for {}", what));
+            }
+            thread::sleep(CONTROL_PERIOD);
+        }
+        Ok(())
+    }
+
+    fn ramp(&self, target: f32, over: f32) -> Result<(), String> {
+        // From the current targets, e.g. the reported motion after a bumpless takeover
+        let from = self.controller.target_velocity();
+        let angle = self.controller.target_steering_tire_angle();
+        let duration = Duration::from_secs_f32(over.max(0.0));
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            self.check_abort()?;
+            let ratio = start.elapsed().as_secs_f32() / over;
+            self.controller
+                .update_control_command(from + (target - from) * ratio, angle);
+            thread::sleep(CONTROL_PERIOD);
+        }
+        self.controller.update_control_command(target, angle);
+        let tolerance = kmh_to_mps(self.script.tolerance);
+        self.wait_for("the target speed", |status| {
+            (status.current_velocity.abs() - target).abs() <= tolerance
+        })
+    }
+
+    fn step(&self, step: &Step) -> Result<(), String> {
+        self.check_abort()?;
+        match *step {
+            Step::Engage => {
+                let status = self.controller.status();
+                if status.gate_mode != gate_mode_data::EXTERNAL {
+                    self.controller.toggle_gate_mode();
+                    if let Some(Err(err)) = self.controller.last_engage() {
+                        return Err(format!("Engage failed: {}", err));
+                    }
+                }
+                self.wait_for("engage", |status| {
+                    status.gate_mode == gate_mode_data::EXTERNAL && status.engage
+                })
+            }
+            Step::Disengage => {
+                if self.controller.status().gate_mode != gate_mode_data::AUTO {
+                    self.controller.toggle_gate_mode();
+                }
+                self.wait_for("AUTO", |status| status.gate_mode == gate_mode_data::AUTO)
+            }
+            Step::Gear(gear) => {
+                self.controller.pub_gear_command(gear.command());
+                self.wait_for("the gear", |status| status.gear == gear.command())
+            }
+            Step::Speed { target, over } => {
+                if target < 0.0 {
+                    return Err("The target speed must be positive, use the gear to reverse".into());
+                }
+                self.ramp(kmh_to_mps(target), over)
+            }
+            Step::Steer { angle, hold } => {
+                self.controller
+                    .update_control_command(self.controller.target_velocity(), angle.to_radians());
+                self.sleep(Duration::from_secs_f32(hold.max(0.0)))
+            }
+            Step::Hold(duration) => self.sleep(Duration::from_secs_f32(duration.max(0.0))),
+            Step::Stop => self.ramp(0.0, 0.0),
+            Step::Park => {
+                self.ramp(0.0, 0.0)?;
+                self.step(&Step::Gear(Gear::Park))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_yaml() {
+        let script = Script::from_yaml(
+            "
+steps:
+  - engage
+  - gear: drive
+  - speed: { target: 20, over: 5 }
+  - steer: { angle: 10, hold: 3 }
+  - hold: 1.5
+  - stop
+  - park
+tolerance: 2
+",
+        )
+        .unwrap();
+        assert_eq!(
+            script.steps,
+            [
+                Step::Engage,
+                Step::Gear(Gear::Drive),
+                Step::Speed {
+                    target: 20.0,
+                    over: 5.0
+                },
+                Step::Steer {
+                    angle: 10.0,
+                    hold: 3.0
+                },
+                Step::Hold(1.5),
+                Step::Stop,
+                Step::Park,
+            ]
+        );
+        assert_eq!(script.tolerance, 2.0);
+        assert_eq!(script.timeout, 30.0);
+    }
+
+    #[test]
+    fn parse_json() {
+        let script =
+            Script::from_json(r#"{"steps": ["engage", {"speed": {"target": 5}}, "stop"]}"#)
+                .unwrap();
+        assert_eq!(
+            script.steps,
+            [
+                Step::Engage,
+                Step::Speed {
+                    target: 5.0,
+                    over: 0.0
+                },
+                Step::Stop,
+            ]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use cdr::{CdrLe, Infinite};
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use zenoh_ros_type::service::ServiceHeader;
+
+use crate::topics::ServiceKey;
+use crate::transport::Transport;
+
+/// Service payload on the DDS bridge topics: the request or reply behind its header
+#[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+struct Raw<T> {
+    header: ServiceHeader,
+    body: T,
+}
+
+/// Payload of a request on the DDS bridge topics, identified by `guid` and `seq`
+pub(crate) fn request_payload<Req: Serialize>(guid: i64, seq: u64, request: &Req) -> Vec<u8> {
+    let raw = Raw {
+        header: ServiceHeader { guid, seq },
+        body: request,
+    };
+    cdr::serialize::<_, _, CdrLe>(&raw, Infinite).unwrap()
+}
+
+/// Body of a reply on the DDS bridge topics, or None if it answers another request
+pub(crate) fn reply_body<Res: DeserializeOwned>(
+    payload: &[u8],
+    guid: i64,
+    seq: u64,
+) -> Option<Res> {
+    let raw = cdr::deserialize_from::<_, Raw<Res>, _>(payload, cdr::size::Infinite).ok()?;
+    (raw.header.guid == guid && raw.header.seq == seq).then_some(raw.body)
+}
+
+/// Decode the reply of the queryable on `key`.
+pub(crate) fn response<Res: DeserializeOwned>(key: &str, payload: &[u8]) -> Result<Res, String> {
+    cdr::deserialize_from::<_, Res, _>(payload, cdr::size::Infinite)
+        .map_err(|err| format!("Unable to deserialize the reply of {}: {:?}", key, err))
+}
+
+/// Call a ROS 2 service and wait at most `timeout` for the response.
+/// `guid` and `seq` identify the request when the service is bridged as DDS topics.
+pub(crate) fn call<Req: Serialize, Res: DeserializeOwned>(
+    transport: &dyn Transport,
+    service: &ServiceKey,
+    guid: i64,
+    seq: u64,
+    request: &Req,
+    timeout: Duration,
+) -> Result<Res, String> {
+    match service {
+        ServiceKey::Query(key) => {
+            let encoded = cdr::serialize::<_, _, CdrLe>(request, Infinite).unwrap();
+            let payload = transport
+                .query(key, encoded, timeout)
+                .map_err(|err| format!("Failed to query {}: {:?}", key, err))?;
+            response(key, &payload)
+        }
+        ServiceKey::Topic {
+            request: key,
+            reply,
+        } => {
+            let (tx, rx) = mpsc::sync_channel(8);
+            let _subscriber = transport
+                .subscribe(
+                    reply,
+                    Box::new(move |payload| {
+                        let _ = tx.try_send(payload.to_vec());
+                    }),
+                )
+                .map_err(|err| format!("Failed to subscribe {}: {:?}", reply, err))?;
+            transport
+                .publish(key, request_payload(guid, seq, request))
+                .map_err(|err| format!("Failed to publish on {}: {:?}", key, err))?;
+            // Skip the replies to the other clients
+            let deadline = Instant::now() + timeout;
+            loop {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                let payload = rx
+                    .recv_timeout(wait)
+                    .map_err(|_| format!("No reply on {}", reply))?;
+                if let Some(body) = reply_body(&payload, guid, seq) {
+                    return Ok(body);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_to_other_requests_are_skipped() {
+        let reply = |guid, seq| {
+            let raw = Raw {
+                header: ServiceHeader { guid, seq },
+                body: 7u32,
+            };
+            cdr::serialize::<_, _, CdrLe>(&raw, Infinite).unwrap()
+        };
+        assert_eq!(reply_body::<u32>(&reply(1, 2), 1, 2), Some(7));
+        assert_eq!(reply_body::<u32>(&reply(9, 2), 1, 2), None);
+        assert_eq!(reply_body::<u32>(&reply(1, 3), 1, 2), None);
+        assert_eq!(reply_body::<u32>(&[0, 1], 1, 2), None);
+    }
+}
@@ -0,0 +1,147 @@
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+/// Controller settings, loaded from a JSON5 file. Every field has a default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub vehicle: VehicleSettings,
+    pub control: ControlSettings,
+    pub routing: RoutingSettings,
+    pub localization: LocalizationSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct VehicleSettings {
+    /// Distance between the front and rear axles (m)
+    pub wheelbase: f32,
+}
+
+impl Default for VehicleSettings {
+    fn default() -> Self {
+        VehicleSettings {
+            wheelbase: 2.79, // sample_vehicle
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    /// Deceleration at full brake (m/s^2)
+    pub max_deceleration: f32,
+    /// Acceleration at full throttle (m/s^2), for the local and remote command sources
+    pub full_throttle_acceleration: f32,
+    /// Speed limit in every gear but reverse (m/s)
+    pub max_speed: f32,
+    /// Speed limit in reverse (m/s)
+    pub max_reverse_speed: f32,
+    /// Speed cap of the yard mode (m/s)
+    pub yard_speed: f32,
+    /// Steering tire angle limit at low speed (radian)
+    pub max_steer_angle: f32,
+    /// Lateral acceleration limiting the steering as the speed rises (m/s^2)
+    pub max_lateral_acceleration: f32,
+    /// Start from the reported speed and steering when taking over from AUTO
+    pub bumpless_takeover: bool,
+    /// Ramp the target speed down to a stop over this time (s) before handing over to AUTO,
+    /// 0 to switch right away
+    pub handover_ramp_time: f32,
+    /// What happens to the targets once the gate mode is back to AUTO
+    pub targets_on_auto: TargetPolicy,
+}
+
+/// What happens to the operator targets when the gate mode switches to AUTO
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetPolicy {
+    /// Zero the speed and steering targets
+    #[default]
+    Reset,
+    /// Keep them for the next time in EXTERNAL
+    Hold,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        ControlSettings {
+            max_deceleration: 3.0,
+            full_throttle_acceleration: 2.0,
+            max_speed: 27.78,        // 100 km/hr
+            max_reverse_speed: 5.56, // 20 km/hr
+            yard_speed: 2.78,        // 10 km/hr
+            max_steer_angle: 0.3925, // 22.5 deg
+            max_lateral_acceleration: 2.0,
+            bumpless_takeover: true,
+            handover_ramp_time: 0.0,
+            targets_on_auto: TargetPolicy::Reset,
+        }
+    }
+}
+
+/// A pose in the map frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct MapPose {
+    /// m
+    pub x: f64,
+    /// m
+    pub y: f64,
+    /// deg, counterclockwise from the x axis
+    #[serde(default)]
+    pub yaw: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RoutingSettings {
+    /// Goal presets by name
+    pub goals: BTreeMap<String, MapPose>,
+    /// Let Autoware move the goal, e.g. to a free parking spot
+    pub allow_goal_modification: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LocalizationSettings {
+    /// Initial pose presets by name
+    pub initial_poses: BTreeMap<String, MapPose>,
+}
+
+impl Settings {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let content = std::fs::read_to_string(path)?;
+        Self::from_json5(&content)
+    }
+
+    pub fn from_json5(content: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(json5::from_str(content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        assert_eq!(Settings::from_json5("{}").unwrap(), Settings::default());
+        let settings = Settings::from_json5("{ vehicle: { wheelbase: 4.0 } }").unwrap();
+        assert_eq!(settings.vehicle.wheelbase, 4.0);
+    }
+
+    #[test]
+    fn target_policy_is_snake_case() {
+        let settings = Settings::from_json5("{ control: { targets_on_auto: 'hold' } }").unwrap();
+        assert_eq!(settings.control.targets_on_auto, TargetPolicy::Hold);
+        assert!(Settings::from_json5("{ control: { targets_on_auto: 'Hold' } }").is_err());
+    }
+
+    #[test]
+    fn sample_file_uses_defaults() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/manual_control.json5");
+        assert_eq!(Settings::from_file(path).unwrap(), Settings::default());
+    }
+}
@@ -0,0 +1,343 @@
+use cdr::{CdrLe, Infinite};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use zenoh_ros_type::{
+    autoware_auto_vehicle_msgs, service, std_msgs, tier4_control_msgs, tier4_external_api_msgs,
+};
+
+use crate::control_loop::{ros_time_now, CONTROL_PERIOD};
+use crate::messages::decode_control_command;
+use crate::topics::{ServiceKey, Topics};
+use crate::transport::{publish, subscribe, Mode, Subscription, Transport};
+use crate::vehicle_model::{advance, Pose};
+
+const MAX_ACCELERATION: f32 = 2.0; // m/s^2
+const MAX_DECELERATION: f32 = 8.0; // m/s^2, full braking
+const MAX_STEER_RATE: f32 = 0.5; // rad/s
+
+/// Vehicle and Autoware state simulated by the stand-in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimState {
+    // pose in the odometry frame
+    pub x: f32,
+    pub y: f32,
+    pub yaw: f32,
+    // vehicle
+    pub velocity: f32,
+    pub steering_tire_angle: f32,
+    pub gear: u8,
+    // Autoware
+    pub gate_mode: u8,
+    pub engage: bool,
+}
+
+impl Default for SimState {
+    fn default() -> Self {
+        SimState {
+            x: 0.0,
+            y: 0.0,
+            yaw: 0.0,
+            velocity: 0.0,
+            steering_tire_angle: 0.0,
+            gear: autoware_auto_vehicle_msgs::gear_command::PARK,
+            gate_mode: tier4_control_msgs::gate_mode_data::AUTO,
+            engage: false,
+        }
+    }
+}
+
+/// Last external control command
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SimCommand {
+    pub speed: f32,
+    /// A negative value sets the deceleration when slowing down
+    pub acceleration: f32,
+    pub steering_tire_angle: f32,
+}
+
+/// Advance `state` by `dt` seconds with a kinematic bicycle model.
+/// The vehicle only follows `command` while engaged in EXTERNAL mode with a moving gear.
+pub fn step(state: &mut SimState, command: &SimCommand, wheelbase: f32, dt: f32) {
+    let active = state.engage
+        && state.gate_mode == tier4_control_msgs::gate_mode_data::EXTERNAL
+        && (state.gear == autoware_auto_vehicle_msgs::gear_command::DRIVE
+            || state.gear == autoware_auto_vehicle_msgs::gear_command::REVERSE);
+    let mut deceleration = MAX_ACCELERATION;
+    let (target_velocity, target_angle) = if active {
+        if command.acceleration < 0.0 {
+            deceleration = (-command.acceleration).min(MAX_DECELERATION);
+        }
+        let target_velocity = if state.gear == autoware_auto_vehicle_msgs::gear_command::DRIVE {
+            command.speed.max(0.0)
+        } else {
+            command.speed.min(0.0)
+        };
+        (target_velocity, command.steering_tire_angle)
+    } else {
+        (0.0, state.steering_tire_angle)
+    };
+    let (max_decrease, max_increase) = if state.velocity >= 0.0 {
+        (deceleration, MAX_ACCELERATION)
+    } else {
+        (MAX_ACCELERATION, deceleration)
+    };
+    state.velocity += num::clamp(
+        target_velocity - state.velocity,
+        -max_decrease * dt,
+        max_increase * dt,
+    );
+    state.steering_tire_angle += num::clamp(
+        target_angle - state.steering_tire_angle,
+        -MAX_STEER_RATE * dt,
+        MAX_STEER_RATE * dt,
+    );
+    let pose = advance(
+        &Pose {
+            x: state.x,
+            y: state.y,
+            yaw: state.yaw,
+        },
+        state.velocity,
+        state.steering_tire_angle,
+        wheelbase,
+        dt,
+    );
+    (state.x, state.y, state.yaw) = (pose.x, pose.y, pose.yaw);
+}
+
+/// Autoware stand-in: answer the controller on the same Zenoh keys as a real Autoware behind the bridge
+pub struct Simulator {
+    // key expressions
+    topics: Topics,
+    // Transport
+    transport: Arc<dyn Transport>,
+    wheelbase: f32,
+    // subscriber and service
+    _subscriptions: Vec<Subscription>,
+    // state
+    state: Arc<Mutex<SimState>>,
+    command: Arc<Mutex<SimCommand>>,
+    // simulation thread
+    running: Arc<AtomicBool>,
+    sim_thread: Option<JoinHandle<()>>,
+}
+
+impl Simulator {
+    pub fn new(transport: Arc<dyn Transport>, mode: Mode, prefix: String, wheelbase: f32) -> Self {
+        Self::with_topics(transport, Topics::new(mode, &prefix), wheelbase)
+    }
+
+    /// Simulate on other keys or message types than the defaults of the bridge mode.
+    pub fn with_topics(transport: Arc<dyn Transport>, topics: Topics, wheelbase: f32) -> Self {
+        let state = Arc::new(Mutex::new(SimState::default()));
+        let command = Arc::new(Mutex::new(SimCommand::default()));
+        let mut subscriptions = Vec::new();
+
+        let sim_state = state.clone();
+        subscriptions.push(subscribe(
+            transport.as_ref(),
+            &topics.gate_mode_cmd,
+            move |gatemode: tier4_control_msgs::GateMode| {
+                log::info!("Gate mode command: {}\r", gatemode.data);
+                sim_state.lock().unwrap().gate_mode = gatemode.data;
+            },
+        ));
+        let sim_state = state.clone();
+        subscriptions.push(subscribe(
+            transport.as_ref(),
+            &topics.gear_cmd,
+            move |gearcmd: autoware_auto_vehicle_msgs::GearCommand| {
+                log::info!("Gear command: {}\r", gearcmd.command);
+                sim_state.lock().unwrap().gear = gearcmd.command;
+            },
+        ));
+        let sim_command = command.clone();
+        let message_set = topics.message_set;
+        subscriptions.push(
+            transport
+                .subscribe(
+                    &topics.control_cmd,
+                    Box::new(move |payload| {
+                        if let Some(cmd) = decode_control_command(message_set, payload) {
+                            *sim_command.lock().unwrap() = SimCommand {
+                                speed: cmd.longitudinal.speed,
+                                acceleration: cmd.longitudinal.acceleration,
+                                steering_tire_angle: cmd.lateral.steering_tire_angle,
+                            };
+                        }
+                    }),
+                )
+                .unwrap(),
+        );
+        subscriptions.push(serve_engage(&transport, &topics.engage, state.clone()));
+
+        Simulator {
+            // key expressions
+            topics,
+            // Transport
+            transport,
+            wheelbase,
+            // subscriber and service
+            _subscriptions: subscriptions,
+            // state
+            state,
+            command,
+            // simulation thread
+            running: Arc::new(AtomicBool::new(false)),
+            sim_thread: None,
+        }
+    }
+
+    pub fn state(&self) -> SimState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Spawn the thread integrating the vehicle and publishing its status at 30 Hz.
+    /// Return false if the thread is already running.
+    pub fn start(&mut self) -> bool {
+        if self.running.swap(true, Ordering::Relaxed) {
+            return false;
+        }
+        let running = self.running.clone();
+        let transport = self.transport.clone();
+        let topics = self.topics.clone();
+        let state = self.state.clone();
+        let command = self.command.clone();
+        let wheelbase = self.wheelbase;
+        self.sim_thread = Some(thread::spawn(move || {
+            while running.load(Ordering::Relaxed) {
+                let current = {
+                    let mut state = state.lock().unwrap();
+                    let command = *command.lock().unwrap();
+                    step(
+                        &mut state,
+                        &command,
+                        wheelbase,
+                        CONTROL_PERIOD.as_secs_f32(),
+                    );
+                    *state
+                };
+                publish_status(transport.as_ref(), &topics, &current);
+                thread::sleep(CONTROL_PERIOD);
+            }
+        }));
+        true
+    }
+
+    /// Stop the simulation thread and wait for it to exit.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.sim_thread.take() {
+            if handle.join().is_err() {
+                log::error!("Simulation thread panicked\r");
+            }
+        }
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn publish_status(transport: &dyn Transport, topics: &Topics, state: &SimState) {
+    let stamp = ros_time_now();
+    publish(
+        transport,
+        &topics.current_gate_mode,
+        &tier4_control_msgs::GateMode {
+            data: state.gate_mode,
+        },
+    );
+    publish(
+        transport,
+        &topics.engage_status,
+        &autoware_auto_vehicle_msgs::Engage {
+            stamp: stamp.clone(),
+            enable: state.engage,
+        },
+    );
+    publish(
+        transport,
+        &topics.gear_status,
+        &autoware_auto_vehicle_msgs::GearReport {
+            stamp: stamp.clone(),
+            report: state.gear,
+        },
+    );
+    publish(
+        transport,
+        &topics.velocity_status,
+        &autoware_auto_vehicle_msgs::VelocityReport {
+            header: std_msgs::Header {
+                stamp,
+                frame_id: "base_link".to_owned(),
+            },
+            longitudinal_velocity: state.velocity,
+            lateral_velocity: 0.0,
+            heading_rate: 0.0,
+        },
+    );
+    publish(
+        transport,
+        &topics.steering_status,
+        &autoware_auto_vehicle_msgs::SteeringReport {
+            stamp: ros_time_now(),
+            steering_tire_angle: state.steering_tire_angle,
+        },
+    );
+}
+
+fn engage_response() -> tier4_external_api_msgs::ResponseStatus {
+    tier4_external_api_msgs::ResponseStatus {
+        code: tier4_external_api_msgs::response_status::SUCCESS,
+        message: String::new(),
+    }
+}
+
+/// Serve the engage service either as a queryable (ROS 2 bridge) or as request/reply topics (DDS bridge).
+fn serve_engage(
+    transport: &Arc<dyn Transport>,
+    key: &ServiceKey,
+    state: Arc<Mutex<SimState>>,
+) -> Subscription {
+    match key {
+        ServiceKey::Query(key) => transport
+            .serve(
+                key,
+                Box::new(move |payload| {
+                    let mode = cdr::deserialize_from::<_, bool, _>(payload, cdr::size::Infinite)
+                        .unwrap_or(false);
+                    log::info!("Engage request: {}\r", mode);
+                    state.lock().unwrap().engage = mode;
+                    let response = tier4_external_api_msgs::EngageResponse {
+                        status: engage_response(),
+                    };
+                    cdr::serialize::<_, _, CdrLe>(&response, Infinite).unwrap()
+                }),
+            )
+            .unwrap(),
+        ServiceKey::Topic { request, reply } => {
+            let reply_transport = transport.clone();
+            let reply = reply.clone();
+            subscribe(
+                transport.as_ref(),
+                request,
+                move |request: tier4_external_api_msgs::RawEngageRequest| {
+                    log::info!("Engage request: {}\r", request.mode);
+                    state.lock().unwrap().engage = request.mode;
+                    let response = tier4_external_api_msgs::RawEngageResponse {
+                        header: service::ServiceHeader {
+                            guid: request.header.guid,
+                            seq: request.header.seq,
+                        },
+                        status: engage_response(),
+                    };
+                    publish(reply_transport.as_ref(), &reply, &response);
+                },
+            )
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use core::fmt;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use zenoh_ros_type::{
+    autoware_auto_vehicle_msgs::{self, control_mode_report},
+    tier4_control_msgs,
+};
+
+use crate::ad_api::operation_mode_name;
+use crate::diagnostics::{hazard_level_name, mrm_behavior, mrm_behavior_name, mrm_state_name, Mrm};
+use crate::localization::initialization_state_name;
+use crate::routing::route_state_name;
+use crate::vehicle_model::Pose;
+
+/// No status sample for this long means the link to Autoware is lost
+pub const LINK_TIMEOUT: Duration = Duration::from_secs(1);
+/// Default velocity thresholds (m/s): stopped or moving
+pub const DEFAULT_VELOCITY_THRESHOLDS: [f32; 1] = [0.1];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkHealth {
+    /// No status sample received yet
+    Unknown,
+    Alive,
+    /// Status samples stopped for longer than `LINK_TIMEOUT`
+    Lost,
+}
+
+/// Vehicle status as reported by Autoware
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControllerStatus {
+    pub gate_mode: u8,
+    /// `control_mode_report` of the vehicle interface, None until reported
+    pub control_mode: Option<u8>,
+    pub engage: bool,
+    /// AD API operation mode, None with the legacy gate mode API
+    pub operation_mode: Option<u8>,
+    /// Routing API route state, None until reported
+    pub route_state: Option<u16>,
+    /// Localization initialization state, None until reported
+    pub localization_state: Option<u16>,
+    /// Minimum risk maneuver, None until reported
+    pub mrm: Option<Mrm>,
+    /// `diagnostics::hazard_level` of the system, None until reported
+    pub hazard_level: Option<u8>,
+    pub gear: u8,
+    /// m/s
+    pub current_velocity: f32,
+    /// radian
+    pub steering_tire_angle: f32,
+    /// Dead-reckoned from the velocity and steering reports
+    pub pose: Pose,
+    pub link: LinkHealth,
+}
+
+impl Default for ControllerStatus {
+    fn default() -> Self {
+        ControllerStatus {
+            gate_mode: tier4_control_msgs::gate_mode_data::AUTO,
+            control_mode: None,
+            engage: false,
+            operation_mode: None,
+            route_state: None,
+            localization_state: None,
+            mrm: None,
+            hazard_level: None,
+            gear: autoware_auto_vehicle_msgs::gear_command::NONE,
+            current_velocity: 0.0,
+            steering_tire_angle: 0.0,
+            pose: Pose::default(),
+            link: LinkHealth::Unknown,
+        }
+    }
+}
+
+pub fn gate_mode_name(gate_mode: u8) -> &'static str {
+    match gate_mode {
+        tier4_control_msgs::gate_mode_data::AUTO => "Auto",
+        tier4_control_msgs::gate_mode_data::EXTERNAL => "External",
+        _ => "Unknown",
+    }
+}
+
+/// Whether `control_mode_report` means the driver took the vehicle over
+pub fn driver_override_mode(mode: u8) -> bool {
+    matches!(
+        mode,
+        control_mode_report::MANUAL | control_mode_report::DISENGAGED
+    )
+}
+
+pub fn control_mode_name(mode: u8) -> &'static str {
+    match mode {
+        control_mode_report::NO_COMMAND => "No command",
+        control_mode_report::AUTONOMOUS => "Autonomous",
+        control_mode_report::AUTONOMOUS_STEER_ONLY => "Autonomous steering only",
+        control_mode_report::AUTONOMOUS_VELOCITY_ONLY => "Autonomous velocity only",
+        control_mode_report::MANUAL => "Manual",
+        control_mode_report::DISENGAGED => "Disengaged",
+        control_mode_report::NOT_READY => "Not ready",
+        _ => "Unknown",
+    }
+}
+
+pub fn gear_name(gear: u8) -> &'static str {
+    match gear {
+        autoware_auto_vehicle_msgs::gear_command::DRIVE => "D",
+        autoware_auto_vehicle_msgs::gear_command::REVERSE => "R",
+        autoware_auto_vehicle_msgs::gear_command::PARK => "P",
+        autoware_auto_vehicle_msgs::gear_command::LOW => "L",
+        _ => "?",
+    }
+}
+
+impl ControllerStatus {
+    /// The vehicle reports that the driver took it over, manually or by disengaging
+    pub fn driver_override(&self) -> bool {
+        self.control_mode.is_some_and(driver_override_mode)
+    }
+}
+
+impl fmt::Display for ControllerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Enage:{}\tGate Mode:{}",
+            if self.engage { "Ready" } else { "Not Ready" },
+            gate_mode_name(self.gate_mode),
+        )?;
+        if let Some(mode) = self.control_mode {
+            write!(f, "\tVehicle:{}", control_mode_name(mode))?;
+        }
+        write!(f, "\tGear:{}", gear_name(self.gear))?;
+        if let Some(mode) = self.operation_mode {
+            write!(f, "\tOperation Mode:{}", operation_mode_name(mode))?;
+        }
+        if let Some(state) = self.route_state {
+            write!(f, "\tRoute:{}", route_state_name(state))?;
+        }
+        if let Some(state) = self.localization_state {
+            write!(f, "\tLocalization:{}", initialization_state_name(state))?;
+        }
+        if let Some(mrm) = self.mrm {
+            write!(f, "\tMRM:{}", mrm)?;
+        }
+        if let Some(level) = self.hazard_level {
+            write!(f, "\tHazard:{}", hazard_level_name(level))?;
+        }
+        if self.link == LinkHealth::Lost {
+            write!(f, "\tLink:Lost")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Mrm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", mrm_state_name(self.state))?;
+        if self.behavior != mrm_behavior::NONE {
+            write!(f, " ({})", mrm_behavior_name(self.behavior))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusEvent {
+    GateModeChanged(u8),
+    ControlModeChanged(u8),
+    EngageChanged(bool),
+    OperationModeChanged(u8),
+    RouteStateChanged(u16),
+    LocalizationStateChanged(u16),
+    MrmChanged(Mrm),
+    HazardLevelChanged(u8),
+    GearChanged(u8),
+    /// The absolute velocity crossed `threshold` (m/s), upwards if `above`
+    VelocityThresholdCrossed {
+        threshold: f32,
+        above: bool,
+    },
+    LinkChanged(LinkHealth),
+}
+
+impl fmt::Display for StatusEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StatusEvent::GateModeChanged(mode) => {
+                write!(f, "Gate Mode => {}", gate_mode_name(*mode))
+            }
+            StatusEvent::ControlModeChanged(mode) => {
+                write!(f, "Vehicle => {}", control_mode_name(*mode))
+            }
+            StatusEvent::EngageChanged(engage) => write!(f, "Engage => {}", engage),
+            StatusEvent::OperationModeChanged(mode) => {
+                write!(f, "Operation Mode => {}", operation_mode_name(*mode))
+            }
+            StatusEvent::RouteStateChanged(state) => {
+                write!(f, "Route => {}", route_state_name(*state))
+            }
+            StatusEvent::LocalizationStateChanged(state) => {
+                write!(f, "Localization => {}", initialization_state_name(*state))
+            }
+            StatusEvent::MrmChanged(mrm) => write!(f, "MRM => {}", mrm),
+            StatusEvent::HazardLevelChanged(level) => {
+                write!(f, "Hazard => {}", hazard_level_name(*level))
+            }
+            StatusEvent::GearChanged(gear) => write!(f, "Gear => {}", gear_name(*gear)),
+            StatusEvent::VelocityThresholdCrossed { threshold, above } => write!(
+                f,
+                "Velocity {} {} km/hr",
+                if *above { "above" } else { "below" },
+                threshold * 3.6
+            ),
+            StatusEvent::LinkChanged(link) => write!(f, "Link => {:?}", link),
+        }
+    }
+}
+
+type Listener = Box<dyn Fn(&StatusEvent) + Send>;
+
+struct TrackerState {
+    status: ControllerStatus,
+    last_sample: Option<Instant>,
+    velocity_thresholds: Vec<f32>,
+}
+
+/// Hold the latest `ControllerStatus` and notify listeners whenever it changes
+pub struct StatusTracker {
+    state: Mutex<TrackerState>,
+    listeners: Mutex<Vec<Listener>>,
+}
+
+impl Default for StatusTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatusTracker {
+    pub fn new() -> Self {
+        StatusTracker {
+            state: Mutex::new(TrackerState {
+                status: ControllerStatus::default(),
+                last_sample: None,
+                velocity_thresholds: DEFAULT_VELOCITY_THRESHOLDS.to_vec(),
+            }),
+            listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn status(&self) -> ControllerStatus {
+        self.state.lock().unwrap().status
+    }
+
+    pub fn set_velocity_thresholds(&self, thresholds: Vec<f32>) {
+        self.state.lock().unwrap().velocity_thresholds = thresholds;
+    }
+
+    /// Call `listener` on every status change. Listeners must not register other listeners.
+    pub fn on_change(&self, listener: impl Fn(&StatusEvent) + Send + 'static) {
+        self.listeners.lock().unwrap().push(Box::new(listener));
+    }
+
+    /// Receive every status change through a channel.
+    pub fn subscribe(&self) -> Receiver<StatusEvent> {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        self.on_change(move |event| {
+            let _ = tx.lock().unwrap().send(*event);
+        });
+        rx
+    }
+
+    /// Apply a freshly received status sample.
+    pub fn update(&self, f: impl FnOnce(&mut ControllerStatus)) {
+        let events = {
+            let mut state = self.state.lock().unwrap();
+            let old = state.status;
+            f(&mut state.status);
+            state.status.link = LinkHealth::Alive;
+            state.last_sample = Some(Instant::now());
+            diff(&old, &state.status, &state.velocity_thresholds)
+        };
+        self.notify(&events);
+    }
+
+    /// Mark the link as lost if no sample arrived within `LINK_TIMEOUT`.
+    pub fn check_link(&self) {
+        let events = {
+            let mut state = self.state.lock().unwrap();
+            match state.last_sample {
+                Some(last) if state.status.link == LinkHealth::Alive => {
+                    if last.elapsed() > LINK_TIMEOUT {
+                        state.status.link = LinkHealth::Lost;
+                        vec![StatusEvent::LinkChanged(LinkHealth::Lost)]
+                    } else {
+                        vec![]
+                    }
+                }
+                _ => vec![],
+            }
+        };
+        self.notify(&events);
+    }
+
+    fn notify(&self, events: &[StatusEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        let listeners = self.listeners.lock().unwrap();
+        for event in events {
+            for listener in listeners.iter() {
+                listener(event);
+            }
+        }
+    }
+}
+
+fn diff(old: &ControllerStatus, new: &ControllerStatus, thresholds: &[f32]) -> Vec<StatusEvent> {
+    let mut events = Vec::new();
+    if old.link != new.link {
+        events.push(StatusEvent::LinkChanged(new.link));
+    }
+    if old.gate_mode != new.gate_mode {
+        events.push(StatusEvent::GateModeChanged(new.gate_mode));
+    }
+    if old.control_mode != new.control_mode {
+        if let Some(mode) = new.control_mode {
+            events.push(StatusEvent::ControlModeChanged(mode));
+        }
+    }
+    if old.engage != new.engage {
+        events.push(StatusEvent::EngageChanged(new.engage));
+    }
+    if old.operation_mode != new.operation_mode {
+        if let Some(mode) = new.operation_mode {
+            events.push(StatusEvent::OperationModeChanged(mode));
+        }
+    }
+    if old.route_state != new.route_state {
+        if let Some(state) = new.route_state {
+            events.push(StatusEvent::RouteStateChanged(state));
+        }
+    }
+    if old.localization_state != new.localization_state {
+        if let Some(state) = new.localization_state {
+            events.push(StatusEvent::LocalizationStateChanged(state));
+        }
+    }
+    if old.mrm != new.mrm {
+        if let Some(mrm) = new.mrm {
+            events.push(StatusEvent::MrmChanged(mrm));
+        }
+    }
+    if old.hazard_level != new.hazard_level {
+        if let Some(level) = new.hazard_level {
+            events.push(StatusEvent::HazardLevelChanged(level));
+        }
+    }
+    if old.gear != new.gear {
+        events.push(StatusEvent::GearChanged(new.gear));
+    }
+    for &threshold in thresholds {
+        let was_above = old.current_velocity.abs() > threshold;
+        let above = new.current_velocity.abs() > threshold;
+        if was_above != above {
+            events.push(StatusEvent::VelocityThresholdCrossed { threshold, above });
+        }
+    }
+    events
+}
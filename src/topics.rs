@@ -0,0 +1,158 @@
+use crate::ad_api::operation_mode;
+use crate::external::CommandSource;
+use crate::messages::MessageSet;
+use crate::transport::Mode;
+
+/// How a ROS 2 service is exposed by the bridge
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServiceKey {
+    /// zenoh-bridge-ros2dds maps a service to a Zenoh queryable
+    Query(String),
+    /// zenoh-bridge-dds maps a service to a pair of request/reply topics
+    Topic { request: String, reply: String },
+}
+
+impl ServiceKey {
+    fn new(mode: Mode, prefix: &str, name: &str) -> Self {
+        match mode {
+            Mode::ROS2 => ServiceKey::Query(prefix.to_owned() + name),
+            Mode::DDS => ServiceKey::Topic {
+                request: prefix.to_owned() + "rq/" + name + "Request",
+                reply: prefix.to_owned() + "rr/" + name + "Reply",
+            },
+        }
+    }
+}
+
+/// Zenoh key expressions of the Autoware topics and services used by the controller
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topics {
+    // publisher
+    pub gate_mode_cmd: String,
+    pub gear_cmd: String,
+    pub control_cmd: String,
+    /// Only published with the local and remote command sources
+    pub heartbeat: String,
+    // subscriber
+    pub current_gate_mode: String,
+    pub engage_status: String,
+    pub control_mode: String,
+    pub gear_status: String,
+    pub velocity_status: String,
+    pub steering_status: String,
+    pub operation_mode_state: String,
+    pub route_state: String,
+    pub localization_initialization_state: String,
+    pub mrm_state: String,
+    pub hazard_status: String,
+    // service
+    pub engage: ServiceKey,
+    pub change_to_stop: ServiceKey,
+    pub change_to_autonomous: ServiceKey,
+    pub change_to_local: ServiceKey,
+    pub change_to_remote: ServiceKey,
+    pub select_external_command: ServiceKey,
+    pub set_route_points: ServiceKey,
+    pub clear_route: ServiceKey,
+    pub initialize_localization: ServiceKey,
+    // message types
+    pub message_set: MessageSet,
+    pub command_source: CommandSource,
+    prefix_rt: String,
+}
+
+impl Topics {
+    /// `prefix` is either empty or ends with '/', see `transport::topic_prefix`.
+    pub fn new(mode: Mode, prefix: &str) -> Self {
+        let prefix_rt = prefix.to_owned() + if mode == Mode::ROS2 { "" } else { "rt/" };
+        Topics {
+            // publisher
+            gate_mode_cmd: prefix_rt.clone() + "control/gate_mode_cmd",
+            gear_cmd: prefix_rt.clone() + "external/selected/gear_cmd",
+            control_cmd: prefix_rt.clone() + "external/selected/control_cmd",
+            heartbeat: prefix_rt.clone() + "external/selected/heartbeat",
+            // subscriber
+            current_gate_mode: prefix_rt.clone() + "control/current_gate_mode",
+            engage_status: prefix_rt.clone() + "api/autoware/get/engage",
+            control_mode: prefix_rt.clone() + "vehicle/status/control_mode",
+            gear_status: prefix_rt.clone() + "vehicle/status/gear_status",
+            velocity_status: prefix_rt.clone() + "vehicle/status/velocity_status",
+            steering_status: prefix_rt.clone() + "vehicle/status/steering_status",
+            operation_mode_state: prefix_rt.clone() + "api/operation_mode/state",
+            route_state: prefix_rt.clone() + "api/routing/state",
+            localization_initialization_state: prefix_rt.clone()
+                + "api/localization/initialization_state",
+            mrm_state: prefix_rt.clone() + "api/fail_safe/mrm_state",
+            hazard_status: prefix_rt.clone() + "system/emergency/hazard_status",
+            // service
+            engage: ServiceKey::new(mode, prefix, "api/autoware/set/engage"),
+            change_to_stop: ServiceKey::new(mode, prefix, "api/operation_mode/change_to_stop"),
+            change_to_autonomous: ServiceKey::new(
+                mode,
+                prefix,
+                "api/operation_mode/change_to_autonomous",
+            ),
+            change_to_local: ServiceKey::new(mode, prefix, "api/operation_mode/change_to_local"),
+            change_to_remote: ServiceKey::new(mode, prefix, "api/operation_mode/change_to_remote"),
+            select_external_command: ServiceKey::new(
+                mode,
+                prefix,
+                "control/external_cmd_selector/select_external_command",
+            ),
+            set_route_points: ServiceKey::new(mode, prefix, "api/routing/set_route_points"),
+            clear_route: ServiceKey::new(mode, prefix, "api/routing/clear_route"),
+            initialize_localization: ServiceKey::new(mode, prefix, "api/localization/initialize"),
+            // message types
+            message_set: MessageSet::default(),
+            command_source: CommandSource::default(),
+            prefix_rt,
+        }
+    }
+
+    /// Publish the gear and control commands on the `external/<source>/*` topics.
+    pub fn with_command_source(mut self, source: CommandSource) -> Self {
+        let external = format!("{}external/{}/", self.prefix_rt, source.name());
+        // external_cmd_selector takes the gear of the local/remote sources as `shift_cmd`
+        self.gear_cmd = match source {
+            CommandSource::Selected => external.clone() + "gear_cmd",
+            CommandSource::Local | CommandSource::Remote => external.clone() + "shift_cmd",
+        };
+        self.control_cmd = external.clone() + "control_cmd";
+        self.heartbeat = external + "heartbeat";
+        self.command_source = source;
+        self
+    }
+
+    pub fn with_message_set(mut self, message_set: MessageSet) -> Self {
+        self.message_set = message_set;
+        self
+    }
+
+    /// The AD API service switching to the operation `mode`
+    pub fn change_operation_mode(&self, mode: u8) -> Option<&ServiceKey> {
+        match mode {
+            operation_mode::STOP => Some(&self.change_to_stop),
+            operation_mode::AUTONOMOUS => Some(&self.change_to_autonomous),
+            operation_mode::LOCAL => Some(&self.change_to_local),
+            operation_mode::REMOTE => Some(&self.change_to_remote),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_source_keys() {
+        let local = Topics::new(Mode::ROS2, "").with_command_source(CommandSource::Local);
+        assert_eq!(local.gear_cmd, "external/local/shift_cmd");
+        assert_eq!(local.control_cmd, "external/local/control_cmd");
+        assert_eq!(local.heartbeat, "external/local/heartbeat");
+        let remote = Topics::new(Mode::DDS, "v1/").with_command_source(CommandSource::Remote);
+        assert_eq!(remote.gear_cmd, "v1/rt/external/remote/shift_cmd");
+        let selected = Topics::new(Mode::ROS2, "").with_command_source(CommandSource::Selected);
+        assert_eq!(selected.gear_cmd, "external/selected/gear_cmd");
+    }
+}
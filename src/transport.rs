@@ -0,0 +1,177 @@
+use core::fmt;
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use cdr::{CdrLe, Infinite};
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Serialize};
+use zenoh::prelude::sync::*;
+use zenoh::publication::Publisher;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Mode {
+    /// Using zenoh-bridge-dds
+    DDS,
+    /// Using zenoh-bridge-ros2dds
+    ROS2,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::DDS => write!(f, "dds"),
+            Mode::ROS2 => write!(f, "ros2"),
+        }
+    }
+}
+
+/// Load the Zenoh configuration from a JSON5 file (or the default one) and add extra listeners.
+pub fn load_config(config_file: Option<&str>, listen: &[String]) -> zenoh::Result<Config> {
+    let mut config = match config_file {
+        Some(conf_file) => Config::from_file(conf_file)?,
+        None => Config::default(),
+    };
+    for endpoint in listen {
+        config.listen.endpoints.push(endpoint.parse()?);
+    }
+    Ok(config)
+}
+
+/// Turn the optional bridge scope into the prefix prepended to every key expression.
+pub fn topic_prefix(prefix: Option<&str>) -> String {
+    match prefix {
+        Some(s) => s.to_string() + "/",
+        None => String::from(""),
+    }
+}
+
+pub fn open_session(config: Config) -> zenoh::Result<Arc<Session>> {
+    Ok(Arc::new(zenoh::open(config).res()?))
+}
+
+/// Called with the payload of every sample received by a subscription
+pub type Callback = Box<dyn Fn(&[u8]) + Send + Sync>;
+
+/// Compute the reply payload of a query from its payload
+pub type QueryHandler = Box<dyn Fn(&[u8]) -> Vec<u8> + Send + Sync>;
+
+/// A live subscription or queryable. Dropping it undeclares it.
+pub struct Subscription {
+    _guard: Box<dyn Send>,
+}
+
+impl Subscription {
+    pub fn new(guard: impl Send + 'static) -> Self {
+        Subscription {
+            _guard: Box::new(guard),
+        }
+    }
+}
+
+/// The publish/subscribe/query primitives the controllers need from the network
+pub trait Transport: Send + Sync {
+    fn publish(&self, key: &str, payload: Vec<u8>) -> zenoh::Result<()>;
+    fn subscribe(&self, key: &str, callback: Callback) -> zenoh::Result<Subscription>;
+    /// Send `payload` to the queryable on `key` and return the payload of the first reply.
+    fn query(&self, key: &str, payload: Vec<u8>, timeout: Duration) -> zenoh::Result<Vec<u8>>;
+    /// Answer the queries on `key` with `handler`.
+    fn serve(&self, key: &str, handler: QueryHandler) -> zenoh::Result<Subscription>;
+}
+
+/// `Transport` over a Zenoh session
+pub struct ZenohTransport {
+    z_session: Arc<Session>,
+    publishers: Mutex<HashMap<String, Publisher<'static>>>,
+}
+
+impl ZenohTransport {
+    pub fn new(z_session: Arc<Session>) -> Self {
+        ZenohTransport {
+            z_session,
+            publishers: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Transport for ZenohTransport {
+    fn publish(&self, key: &str, payload: Vec<u8>) -> zenoh::Result<()> {
+        let mut publishers = self.publishers.lock().unwrap();
+        let publisher = match publishers.entry(key.to_owned()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(self.z_session.declare_publisher(key.to_owned()).res()?)
+            }
+        };
+        publisher.put(payload).res()
+    }
+
+    fn subscribe(&self, key: &str, callback: Callback) -> zenoh::Result<Subscription> {
+        let subscriber = self
+            .z_session
+            .declare_subscriber(key.to_owned())
+            .callback(move |sample| callback(&sample.payload.contiguous()))
+            .res()?;
+        Ok(Subscription::new(subscriber))
+    }
+
+    fn query(&self, key: &str, payload: Vec<u8>, timeout: Duration) -> zenoh::Result<Vec<u8>> {
+        let replies = self
+            .z_session
+            .get(key)
+            .with_value(payload)
+            .timeout(timeout)
+            .res()?;
+        let reply = replies.recv()?;
+        match reply.sample {
+            Ok(sample) => Ok(sample.payload.contiguous().to_vec()),
+            Err(err) => Err(format!("Error reply: {:?}", err).into()),
+        }
+    }
+
+    fn serve(&self, key: &str, handler: QueryHandler) -> zenoh::Result<Subscription> {
+        let reply_key = KeyExpr::try_from(key.to_owned())?;
+        let queryable = self
+            .z_session
+            .declare_queryable(key.to_owned())
+            .callback(move |query| {
+                let payload = query
+                    .value()
+                    .map(|value| value.payload.contiguous().to_vec())
+                    .unwrap_or_default();
+                let sample = Sample::new(reply_key.clone(), handler(&payload));
+                if let Err(err) = query.reply(Ok(sample)).res() {
+                    log::error!("Failed to reply to {}: {:?}\r", reply_key, err);
+                }
+            })
+            .res()?;
+        Ok(Subscription::new(queryable))
+    }
+}
+
+/// CDR-encode and publish `msg`, logging instead of failing.
+pub(crate) fn publish<T: Serialize>(transport: &dyn Transport, key: &str, msg: &T) {
+    let encoded = cdr::serialize::<_, _, CdrLe>(msg, Infinite).unwrap();
+    if let Err(err) = transport.publish(key, encoded) {
+        log::error!("Failed to publish on {}: {:?}\r", key, err);
+    }
+}
+
+/// Subscribe to `key` and call `f` with every sample decoded as `T`.
+pub(crate) fn subscribe<T: DeserializeOwned>(
+    transport: &dyn Transport,
+    key: &str,
+    f: impl Fn(T) + Send + Sync + 'static,
+) -> Subscription {
+    transport
+        .subscribe(
+            key,
+            Box::new(move |payload| {
+                if let Ok(msg) = cdr::deserialize_from::<_, T, _>(payload, cdr::size::Infinite) {
+                    f(msg);
+                }
+            }),
+        )
+        .unwrap()
+}
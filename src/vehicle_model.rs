@@ -0,0 +1,139 @@
+use std::time::Instant;
+
+/// Minimum length of the predicted path, so the arc stays visible when stopped (m)
+const MIN_PREDICTION_DISTANCE: f32 = 5.0;
+const PREDICTION_STEPS: usize = 20;
+/// Ignore gaps between samples longer than this, e.g. after a link loss (s)
+const MAX_INTEGRATION_STEP: f32 = 0.5;
+
+/// 2D pose. `yaw` is counter-clockwise from the x axis (radian).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pose {
+    pub x: f32,
+    pub y: f32,
+    pub yaw: f32,
+}
+
+/// Move `pose` along a kinematic bicycle model for `dt` seconds.
+pub fn advance(
+    pose: &Pose,
+    velocity: f32,
+    steering_tire_angle: f32,
+    wheelbase: f32,
+    dt: f32,
+) -> Pose {
+    let yaw = pose.yaw + velocity / wheelbase * steering_tire_angle.tan() * dt;
+    Pose {
+        x: pose.x + velocity * yaw.cos() * dt,
+        y: pose.y + velocity * yaw.sin() * dt,
+        yaw,
+    }
+}
+
+/// Dead-reckon the vehicle pose from the reported velocity and steering angle
+pub struct VehicleModel {
+    wheelbase: f32,
+    pose: Pose,
+    last_update: Option<Instant>,
+}
+
+impl VehicleModel {
+    pub fn new(wheelbase: f32) -> Self {
+        VehicleModel {
+            wheelbase,
+            pose: Pose::default(),
+            last_update: None,
+        }
+    }
+
+    pub fn wheelbase(&self) -> f32 {
+        self.wheelbase
+    }
+
+    pub fn set_wheelbase(&mut self, wheelbase: f32) {
+        self.wheelbase = wheelbase;
+    }
+
+    /// Estimated pose relative to where the controller started (or was last reset)
+    pub fn pose(&self) -> Pose {
+        self.pose
+    }
+
+    pub fn reset(&mut self) {
+        self.pose = Pose::default();
+        self.last_update = None;
+    }
+
+    /// Integrate the reported state since the previous update and return the new pose.
+    pub fn update(&mut self, velocity: f32, steering_tire_angle: f32, now: Instant) -> Pose {
+        if let Some(last) = self.last_update {
+            let dt = now.duration_since(last).as_secs_f32();
+            if dt <= MAX_INTEGRATION_STEP {
+                self.pose = advance(
+                    &self.pose,
+                    velocity,
+                    steering_tire_angle,
+                    self.wheelbase,
+                    dt,
+                );
+            }
+        }
+        self.last_update = Some(now);
+        self.pose
+    }
+
+    /// Path the vehicle would follow in the next `horizon` seconds if velocity and steering stay,
+    /// in the vehicle frame (x forward, y left).
+    pub fn predict_path(&self, velocity: f32, steering_tire_angle: f32, horizon: f32) -> Vec<Pose> {
+        let distance = (velocity.abs() * horizon).max(MIN_PREDICTION_DISTANCE);
+        let direction = if velocity < 0.0 { -1.0 } else { 1.0 };
+        let step = distance / PREDICTION_STEPS as f32;
+        let mut pose = Pose::default();
+        let mut path = vec![pose];
+        for _ in 0..PREDICTION_STEPS {
+            pose = advance(&pose, direction, steering_tire_angle, self.wheelbase, step);
+            path.push(pose);
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn straight_line() {
+        let pose = advance(&Pose::default(), 2.0, 0.0, 2.79, 1.5);
+        assert_eq!(
+            pose,
+            Pose {
+                x: 3.0,
+                y: 0.0,
+                yaw: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn left_turn_follows_circle() {
+        let wheelbase = 2.79;
+        let steering = 0.3_f32;
+        let radius = wheelbase / steering.tan();
+        let model = VehicleModel::new(wheelbase);
+        let path = model.predict_path(1.0, steering, 5.0);
+        for pose in path {
+            // Center of the turning circle is on the left at (0, radius)
+            let distance = (pose.x.powi(2) + (pose.y - radius).powi(2)).sqrt();
+            assert!((distance - radius).abs() < 0.2, "{:?}", pose);
+            assert!(pose.y >= 0.0);
+        }
+    }
+
+    #[test]
+    fn reverse_path_goes_backward() {
+        let model = VehicleModel::new(2.79);
+        let path = model.predict_path(-1.0, 0.0, 10.0);
+        assert!((path.last().unwrap().x + 10.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,835 @@
+use autoware_manual_control::ad_api::{
+    operation_mode, EmptyRequest, OperationModeState, ResponseStatus as AdApiStatus, StatusResponse,
+};
+use autoware_manual_control::diagnostics::{
+    hazard_level, mrm_behavior, mrm_state, DiagnosticStatus, HazardStatus, HazardStatusStamped,
+    Mrm, MrmState,
+};
+use autoware_manual_control::external::{
+    gear_shift, selector_mode, ControlCommandStamped, ExternalCommandSelectRequest,
+    ExternalCommandSelectResponse, GearShiftStamped,
+};
+use autoware_manual_control::localization::{
+    initialization_state, InitializeLocalizationRequest, LocalizationInitializationState,
+};
+use autoware_manual_control::routing::{route_state, RouteState, SetRoutePointsRequest};
+use autoware_manual_control::{
+    CommandSource, ControlApi, CruiseState, EngageError, EngageStatus, ManualController, MapPose,
+    MockTransport, Mode, PublishPolicy, ResponseCode, Settings, StatusEvent, TargetPolicy,
+    Transport,
+};
+use cdr::{CdrLe, Infinite};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use zenoh_ros_type::{
+    autoware_auto_control_msgs::AckermannControlCommand,
+    autoware_auto_vehicle_msgs::{
+        control_mode_report, gear_command, ControlModeReport, GearCommand, SteeringReport,
+        VelocityReport,
+    },
+    builtin_interfaces::Time,
+    service::ServiceHeader,
+    std_msgs::Header,
+    tier4_control_msgs::{gate_mode_data, GateMode},
+    tier4_external_api_msgs::{
+        response_status, EngageResponse, RawEngageRequest, RawEngageResponse, ResponseStatus,
+    },
+};
+
+fn encode<T: serde::Serialize>(msg: &T) -> Vec<u8> {
+    cdr::serialize::<_, _, CdrLe>(msg, Infinite).unwrap()
+}
+
+fn decode<T: serde::de::DeserializeOwned>(payload: &[u8]) -> T {
+    cdr::deserialize_from::<_, T, _>(payload, cdr::size::Infinite).unwrap()
+}
+
+fn new_controller(mode: Mode) -> (Arc<MockTransport>, ManualController) {
+    let transport = Arc::new(MockTransport::new());
+    let controller = ManualController::with_transport(transport.clone(), mode, String::new());
+    (transport, controller)
+}
+
+fn success() -> ResponseStatus {
+    ResponseStatus {
+        code: response_status::SUCCESS,
+        message: String::new(),
+    }
+}
+
+#[test]
+fn toggle_sends_gate_mode_and_engage_query() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    let _service = transport
+        .serve(
+            "api/autoware/set/engage",
+            Box::new(|payload| {
+                assert!(decode::<bool>(payload));
+                encode(&EngageResponse { status: success() })
+            }),
+        )
+        .unwrap();
+    controller.init();
+
+    assert!(controller.toggle_gate_mode());
+    let gate_mode: GateMode = transport.last_published("control/gate_mode_cmd").unwrap();
+    assert_eq!(gate_mode.data, gate_mode_data::EXTERNAL);
+    assert_eq!(
+        controller.last_engage().unwrap().unwrap().code,
+        ResponseCode::Success
+    );
+
+    transport.inject_msg("control/current_gate_mode", &gate_mode);
+    assert!(!controller.toggle_gate_mode());
+    let gate_mode: GateMode = transport.last_published("control/gate_mode_cmd").unwrap();
+    assert_eq!(gate_mode.data, gate_mode_data::AUTO);
+}
+
+#[test]
+fn engage_over_dds_request_reply_topics() {
+    let (transport, mut controller) = new_controller(Mode::DDS);
+    let responder = transport.clone();
+    let _service = transport
+        .subscribe(
+            "rq/api/autoware/set/engageRequest",
+            Box::new(move |payload| {
+                let request: RawEngageRequest = decode(payload);
+                assert!(request.mode);
+                let reply = RawEngageResponse {
+                    header: ServiceHeader {
+                        guid: request.header.guid,
+                        seq: request.header.seq,
+                    },
+                    status: success(),
+                };
+                responder.inject("rr/api/autoware/set/engageReply", encode(&reply));
+            }),
+        )
+        .unwrap();
+    controller.init();
+
+    controller.toggle_gate_mode();
+    controller.toggle_gate_mode();
+    let requests = transport.published("rq/api/autoware/set/engageRequest");
+    assert_eq!(requests.len(), 2);
+    let first: RawEngageRequest = decode(&requests[0]);
+    let second: RawEngageRequest = decode(&requests[1]);
+    assert_eq!(first.header.guid, second.header.guid);
+    assert_eq!(second.header.seq, first.header.seq + 1);
+}
+
+#[test]
+fn engage_errors_are_typed() {
+    let (transport, controller) = new_controller(Mode::ROS2);
+    assert!(matches!(
+        controller.engage(),
+        Err(EngageError::NoResponse(_))
+    ));
+    let _service = transport
+        .serve(
+            "api/autoware/set/engage",
+            Box::new(|_| {
+                encode(&EngageResponse {
+                    status: ResponseStatus {
+                        code: response_status::ERROR,
+                        message: "vehicle is not ready".to_owned(),
+                    },
+                })
+            }),
+        )
+        .unwrap();
+    let err = controller.engage().unwrap_err();
+    assert_eq!(
+        err,
+        EngageError::Rejected(EngageStatus {
+            code: ResponseCode::Error,
+            message: "vehicle is not ready".to_owned(),
+        })
+    );
+    assert_eq!(controller.last_engage(), Some(Err(err)));
+}
+
+fn operation_mode_state(mode: u8) -> OperationModeState {
+    OperationModeState {
+        stamp: Time { sec: 0, nanosec: 0 },
+        mode,
+        is_autoware_control_enabled: true,
+        is_in_transition: false,
+        is_stop_mode_available: true,
+        is_autonomous_mode_available: true,
+        is_local_mode_available: true,
+        is_remote_mode_available: true,
+    }
+}
+
+fn changed() -> StatusResponse {
+    StatusResponse {
+        status: AdApiStatus {
+            success: true,
+            code: 0,
+            message: String::new(),
+        },
+    }
+}
+
+#[test]
+fn ad_api_toggle_changes_operation_mode() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    controller.set_control_api(ControlApi::AdApi);
+    let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut services = Vec::new();
+    for (key, mode) in [
+        ("api/operation_mode/change_to_local", operation_mode::LOCAL),
+        (
+            "api/operation_mode/change_to_autonomous",
+            operation_mode::AUTONOMOUS,
+        ),
+    ] {
+        let calls = calls.clone();
+        services.push(
+            transport
+                .serve(
+                    key,
+                    Box::new(move |payload| {
+                        let _: EmptyRequest = decode(payload);
+                        calls.lock().unwrap().push(mode);
+                        encode(&changed())
+                    }),
+                )
+                .unwrap(),
+        );
+    }
+    controller.init();
+    transport.inject_msg(
+        "api/operation_mode/state",
+        &operation_mode_state(operation_mode::AUTONOMOUS),
+    );
+
+    assert!(controller.toggle_gate_mode());
+    transport.inject_msg(
+        "api/operation_mode/state",
+        &operation_mode_state(operation_mode::LOCAL),
+    );
+    let status = controller.status();
+    assert_eq!(status.operation_mode, Some(operation_mode::LOCAL));
+    assert_eq!(status.gate_mode, gate_mode_data::EXTERNAL);
+    assert!(!controller.toggle_gate_mode());
+    assert_eq!(
+        *calls.lock().unwrap(),
+        [operation_mode::LOCAL, operation_mode::AUTONOMOUS]
+    );
+    // The legacy gate mode is left alone
+    assert!(transport.published("control/gate_mode_cmd").is_empty());
+}
+
+#[test]
+fn ad_api_over_dds_matches_the_reply_to_the_request() {
+    #[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+    struct Raw<T> {
+        header: ServiceHeader,
+        body: T,
+    }
+    let (transport, controller) = new_controller(Mode::DDS);
+    let responder = transport.clone();
+    let _service = transport
+        .subscribe(
+            "rq/api/operation_mode/change_to_stopRequest",
+            Box::new(move |payload| {
+                let request: Raw<EmptyRequest> = decode(payload);
+                // A reply to another client first
+                let mut reply = Raw {
+                    header: ServiceHeader {
+                        guid: request.header.guid + 1,
+                        seq: request.header.seq,
+                    },
+                    body: StatusResponse::default(),
+                };
+                responder.inject("rr/api/operation_mode/change_to_stopReply", encode(&reply));
+                reply.header.guid = request.header.guid;
+                reply.body = changed();
+                responder.inject("rr/api/operation_mode/change_to_stopReply", encode(&reply));
+            }),
+        )
+        .unwrap();
+    assert_eq!(
+        controller.change_operation_mode(operation_mode::STOP),
+        Ok(())
+    );
+}
+
+#[test]
+fn local_source_selects_and_sends_pedals() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    controller.set_command_source(CommandSource::Local);
+    let _selector = transport
+        .serve(
+            "control/external_cmd_selector/select_external_command",
+            Box::new(|payload| {
+                let request: ExternalCommandSelectRequest = decode(payload);
+                assert_eq!(request.mode, selector_mode::LOCAL);
+                encode(&ExternalCommandSelectResponse {
+                    success: true,
+                    message: String::new(),
+                })
+            }),
+        )
+        .unwrap();
+    let _engage = transport
+        .serve(
+            "api/autoware/set/engage",
+            Box::new(|_| encode(&EngageResponse { status: success() })),
+        )
+        .unwrap();
+    controller.init();
+    assert!(controller.toggle_gate_mode());
+    assert_eq!(controller.select_external_command(), Ok(()));
+
+    controller.pub_gear_command(gear_command::DRIVE);
+    let gear: GearShiftStamped = transport
+        .last_published("external/local/shift_cmd")
+        .unwrap();
+    assert_eq!(gear.gear_shift, gear_shift::DRIVE);
+    controller.update_control_command(5.0, 0.1);
+    thread::sleep(Duration::from_millis(200));
+    let cmd: ControlCommandStamped = transport
+        .last_published("external/local/control_cmd")
+        .unwrap();
+    assert!(cmd.control.throttle > 0.0);
+    assert_eq!(cmd.control.brake, 0.0);
+    assert!(!transport.published("external/local/heartbeat").is_empty());
+    assert!(transport
+        .published("external/selected/control_cmd")
+        .is_empty());
+}
+
+#[test]
+fn set_goal_clears_the_route_then_sends_the_pose() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let cleared = calls.clone();
+    let _clear = transport
+        .serve(
+            "api/routing/clear_route",
+            Box::new(move |payload| {
+                let _: EmptyRequest = decode(payload);
+                cleared.lock().unwrap().push("clear");
+                encode(&changed())
+            }),
+        )
+        .unwrap();
+    let set = calls.clone();
+    let _set = transport
+        .serve(
+            "api/routing/set_route_points",
+            Box::new(move |payload| {
+                let request: SetRoutePointsRequest = decode(payload);
+                assert_eq!(request.header.frame_id, "map");
+                assert_eq!(request.goal.position.x, 10.0);
+                assert_eq!(request.goal.position.y, -2.0);
+                assert!((request.goal.orientation.z - 1.0).abs() < 1e-9);
+                assert!(request.waypoints.is_empty());
+                set.lock().unwrap().push("set");
+                encode(&changed())
+            }),
+        )
+        .unwrap();
+    controller.init();
+    transport.inject_msg(
+        "api/routing/state",
+        &RouteState {
+            stamp: Time { sec: 0, nanosec: 0 },
+            state: route_state::SET,
+        },
+    );
+    assert_eq!(controller.status().route_state, Some(route_state::SET));
+
+    let goal = MapPose {
+        x: 10.0,
+        y: -2.0,
+        yaw: 180.0,
+    };
+    assert_eq!(controller.set_goal(&goal), Ok(()));
+    assert_eq!(*calls.lock().unwrap(), ["clear", "set"]);
+
+    for (state, expected) in [
+        (route_state::ARRIVED, vec!["clear", "set"]),
+        (route_state::UNSET, vec!["set"]),
+    ] {
+        calls.lock().unwrap().clear();
+        transport.inject_msg(
+            "api/routing/state",
+            &RouteState {
+                stamp: Time { sec: 0, nanosec: 0 },
+                state,
+            },
+        );
+        assert_eq!(controller.set_goal(&goal), Ok(()));
+        assert_eq!(*calls.lock().unwrap(), expected);
+    }
+}
+
+#[test]
+fn initial_pose_initializes_the_localization() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    let _service = transport
+        .serve(
+            "api/localization/initialize",
+            Box::new(|payload| {
+                let request: InitializeLocalizationRequest = decode(payload);
+                assert_eq!(request.pose.len(), 1);
+                let pose = &request.pose[0];
+                assert_eq!(pose.header.frame_id, "map");
+                assert_eq!(pose.pose.pose.position.x, 3.0);
+                assert_eq!(pose.pose.pose.position.y, 4.0);
+                assert_eq!(pose.pose.covariance[0][0], 0.25);
+                encode(&changed())
+            }),
+        )
+        .unwrap();
+    controller.init();
+    transport.inject_msg(
+        "api/localization/initialization_state",
+        &LocalizationInitializationState {
+            stamp: Time { sec: 0, nanosec: 0 },
+            state: initialization_state::UNINITIALIZED,
+        },
+    );
+    assert_eq!(
+        controller.status().localization_state,
+        Some(initialization_state::UNINITIALIZED)
+    );
+
+    let pose = MapPose {
+        x: 3.0,
+        y: 4.0,
+        yaw: 0.0,
+    };
+    assert_eq!(controller.initialize_localization(&pose), Ok(()));
+}
+
+#[test]
+fn mrm_and_faults_are_reported() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    let events = controller.subscribe_status();
+    controller.init();
+    transport.inject_msg(
+        "api/fail_safe/mrm_state",
+        &MrmState {
+            stamp: Time { sec: 0, nanosec: 0 },
+            state: mrm_state::MRM_OPERATING,
+            behavior: mrm_behavior::EMERGENCY_STOP,
+        },
+    );
+    transport.inject_msg(
+        "system/emergency/hazard_status",
+        &HazardStatusStamped {
+            stamp: Time { sec: 0, nanosec: 0 },
+            status: HazardStatus {
+                level: hazard_level::SINGLE_POINT_FAULT,
+                emergency: true,
+                emergency_holding: false,
+                diag_no_fault: Vec::new(),
+                diag_safe_fault: Vec::new(),
+                diag_latent_fault: Vec::new(),
+                diag_single_point_fault: vec![DiagnosticStatus {
+                    level: 2,
+                    name: "/autoware/localization/node_alive_monitoring".to_owned(),
+                    message: "timeout".to_owned(),
+                    hardware_id: String::new(),
+                    values: Vec::new(),
+                }],
+            },
+        },
+    );
+
+    let mrm = Mrm {
+        state: mrm_state::MRM_OPERATING,
+        behavior: mrm_behavior::EMERGENCY_STOP,
+    };
+    let status = controller.status();
+    assert_eq!(status.mrm, Some(mrm));
+    assert_eq!(status.hazard_level, Some(hazard_level::SINGLE_POINT_FAULT));
+    assert!(status
+        .to_string()
+        .contains("MRM:Operating (Emergency stop)\tHazard:Single point fault"));
+    let faults = controller.faults();
+    assert_eq!(faults.len(), 1);
+    assert_eq!(faults[0].message, "timeout");
+    let received: Vec<_> = events.try_iter().collect();
+    assert!(received.contains(&StatusEvent::MrmChanged(mrm)));
+    assert!(received.contains(&StatusEvent::HazardLevelChanged(
+        hazard_level::SINGLE_POINT_FAULT
+    )));
+}
+
+#[test]
+fn gear_command_is_published() {
+    let (transport, controller) = new_controller(Mode::ROS2);
+    controller.pub_gear_command(gear_command::REVERSE);
+    let gear: GearCommand = transport
+        .last_published("external/selected/gear_cmd")
+        .unwrap();
+    assert_eq!(gear.command, gear_command::REVERSE);
+
+    let (transport, controller) = new_controller(Mode::DDS);
+    controller.pub_gear_command(gear_command::DRIVE);
+    let gear: GearCommand = transport
+        .last_published("rt/external/selected/gear_cmd")
+        .unwrap();
+    assert_eq!(gear.command, gear_command::DRIVE);
+}
+
+#[test]
+fn control_command_encodes_targets() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    controller.init();
+    transport.inject_msg(
+        "vehicle/status/gear_status",
+        &GearCommand {
+            stamp: Time { sec: 0, nanosec: 0 },
+            command: gear_command::REVERSE,
+        },
+    );
+    controller.update_control_command(2.0, -0.2);
+    thread::sleep(Duration::from_millis(200));
+
+    let cmd: AckermannControlCommand = transport
+        .last_published("external/selected/control_cmd")
+        .unwrap();
+    assert_eq!(cmd.longitudinal.speed, -2.0);
+    assert_eq!(cmd.longitudinal.acceleration, 1.0);
+    assert_eq!(cmd.lateral.steering_tire_angle, -0.2);
+}
+
+#[test]
+fn yard_mode_caps_the_published_speed() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    controller.init();
+    transport.inject_msg(
+        "vehicle/status/gear_status",
+        &GearCommand {
+            stamp: Time { sec: 0, nanosec: 0 },
+            command: gear_command::DRIVE,
+        },
+    );
+    controller.update_control_command(20.0, 0.0);
+    assert!(controller.toggle_yard_mode());
+    assert_eq!(controller.speed_cap(), Some(2.78));
+    thread::sleep(Duration::from_millis(200));
+    let cmd: AckermannControlCommand = transport
+        .last_published("external/selected/control_cmd")
+        .unwrap();
+    assert_eq!(cmd.longitudinal.speed, 2.78);
+
+    assert!(!controller.toggle_yard_mode());
+    assert_eq!(controller.speed_limit(), 27.78);
+}
+
+#[test]
+fn lifecycle_controls_publishing() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    assert!(controller.init());
+    assert!(!controller.init());
+    assert!(controller.is_running());
+
+    controller.pause();
+    thread::sleep(Duration::from_millis(100));
+    transport.clear_published();
+    thread::sleep(Duration::from_millis(100));
+    assert!(transport
+        .published("external/selected/control_cmd")
+        .is_empty());
+
+    controller.resume();
+    thread::sleep(Duration::from_millis(100));
+    assert!(!transport
+        .published("external/selected/control_cmd")
+        .is_empty());
+
+    controller.stop();
+    assert!(!controller.is_running());
+}
+
+#[test]
+fn external_only_policy_stays_silent_in_auto() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    controller.set_publish_policy(PublishPolicy::ExternalOnly);
+    controller.init();
+    thread::sleep(Duration::from_millis(150));
+    assert!(transport
+        .published("external/selected/control_cmd")
+        .is_empty());
+}
+
+#[test]
+fn status_changes_are_reported() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    let events = controller.subscribe_status();
+    controller.init();
+    transport.inject_msg(
+        "vehicle/status/gear_status",
+        &GearCommand {
+            stamp: Time { sec: 0, nanosec: 0 },
+            command: gear_command::DRIVE,
+        },
+    );
+    let received: Vec<StatusEvent> = events.try_iter().collect();
+    assert!(received.contains(&StatusEvent::GearChanged(gear_command::DRIVE)));
+    assert_eq!(controller.status().gear, gear_command::DRIVE);
+}
+
+#[test]
+fn driver_override_blocks_motion_commands() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    controller.init();
+    controller.update_control_command(5.0, 0.1);
+    transport.inject_msg(
+        "vehicle/status/control_mode",
+        &ControlModeReport {
+            stamp: Time { sec: 0, nanosec: 0 },
+            mode: control_mode_report::MANUAL,
+        },
+    );
+    assert!(controller.motion_blocked());
+    assert!(controller.status().to_string().contains("Vehicle:Manual"));
+    // Nothing left to resume when the vehicle is handed back
+    assert_eq!(controller.target_velocity(), 0.0);
+    controller.update_control_command(5.0, 0.1);
+    assert_eq!(controller.target_velocity(), 0.0);
+    thread::sleep(Duration::from_millis(100));
+    let cmd: AckermannControlCommand = transport
+        .last_published("external/selected/control_cmd")
+        .unwrap();
+    assert_eq!(cmd.longitudinal.speed, 0.0);
+
+    transport.inject_msg(
+        "vehicle/status/control_mode",
+        &ControlModeReport {
+            stamp: Time { sec: 0, nanosec: 0 },
+            mode: control_mode_report::AUTONOMOUS,
+        },
+    );
+    controller.update_control_command(5.0, 0.1);
+    assert_eq!(controller.target_velocity(), 5.0);
+}
+
+#[test]
+fn takeover_starts_from_the_reported_motion() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    controller.set_publish_policy(PublishPolicy::ExternalOnly);
+    controller.init();
+    transport.inject_msg(
+        "vehicle/status/gear_status",
+        &GearCommand {
+            stamp: Time { sec: 0, nanosec: 0 },
+            command: gear_command::DRIVE,
+        },
+    );
+    transport.inject_msg(
+        "vehicle/status/velocity_status",
+        &VelocityReport {
+            header: Header {
+                stamp: Time { sec: 0, nanosec: 0 },
+                frame_id: String::new(),
+            },
+            longitudinal_velocity: 8.0,
+            lateral_velocity: 0.0,
+            heading_rate: 0.0,
+        },
+    );
+    transport.inject_msg(
+        "vehicle/status/steering_status",
+        &SteeringReport {
+            stamp: Time { sec: 0, nanosec: 0 },
+            steering_tire_angle: 0.02,
+        },
+    );
+    assert!(controller.toggle_gate_mode());
+    assert_eq!(controller.target_velocity(), 8.0);
+    assert_eq!(controller.target_steering_tire_angle(), 0.02);
+    transport.inject_msg(
+        "control/current_gate_mode",
+        &GateMode {
+            data: gate_mode_data::EXTERNAL,
+        },
+    );
+    thread::sleep(Duration::from_millis(100));
+
+    assert_eq!(controller.target_velocity(), 8.0);
+    assert_eq!(controller.target_steering_tire_angle(), 0.02);
+    let commands = transport.published("external/selected/control_cmd");
+    let first: AckermannControlCommand = decode(&commands[0]);
+    assert_eq!(first.longitudinal.speed, 8.0);
+}
+
+#[test]
+fn targets_follow_the_policy_back_in_auto() {
+    for policy in [TargetPolicy::Reset, TargetPolicy::Hold] {
+        let (transport, mut controller) = new_controller(Mode::ROS2);
+        let mut settings = Settings::default();
+        settings.control.bumpless_takeover = false;
+        settings.control.targets_on_auto = policy;
+        controller.set_settings(settings);
+        controller.init();
+        let gate = |data| GateMode { data };
+        transport.inject_msg("control/current_gate_mode", &gate(gate_mode_data::EXTERNAL));
+        thread::sleep(Duration::from_millis(100));
+        controller.update_control_command(5.0, 0.1);
+        transport.inject_msg("control/current_gate_mode", &gate(gate_mode_data::AUTO));
+        thread::sleep(Duration::from_millis(100));
+
+        let expected = match policy {
+            TargetPolicy::Reset => (0.0, 0.0),
+            TargetPolicy::Hold => (5.0, 0.1),
+        };
+        assert_eq!(
+            (
+                controller.target_velocity(),
+                controller.target_steering_tire_angle()
+            ),
+            expected
+        );
+        assert_eq!(controller.pending_targets(), expected);
+        assert!(controller.get_status().ends_with(&format!(
+            "Pending:{:.1} km/hr, {:.1} deg",
+            expected.0 * 3.6,
+            expected.1.to_degrees()
+        )));
+        controller.stop();
+    }
+}
+
+#[test]
+fn handover_ramps_down_before_switching_to_auto() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    let mut settings = Settings::default();
+    settings.control.handover_ramp_time = 0.3;
+    controller.set_settings(settings);
+    controller.set_publish_policy(PublishPolicy::ExternalOnly);
+    controller.init();
+    transport.inject_msg(
+        "vehicle/status/gear_status",
+        &GearCommand {
+            stamp: Time { sec: 0, nanosec: 0 },
+            command: gear_command::DRIVE,
+        },
+    );
+    transport.inject_msg(
+        "control/current_gate_mode",
+        &GateMode {
+            data: gate_mode_data::EXTERNAL,
+        },
+    );
+    controller.update_control_command(5.0, 0.1);
+    thread::sleep(Duration::from_millis(100));
+
+    let start = Instant::now();
+    assert!(!controller.toggle_gate_mode());
+    // The control thread ramps down, the toggle doesn't wait for it
+    assert!(start.elapsed() < Duration::from_millis(100));
+    assert!(controller.handing_over());
+    assert!(transport
+        .last_published::<GateMode>(&controller.topics().gate_mode_cmd)
+        .is_none());
+    thread::sleep(Duration::from_millis(500));
+    assert!(!controller.handing_over());
+    let gate_mode: GateMode = transport
+        .last_published(&controller.topics().gate_mode_cmd)
+        .unwrap();
+    assert_eq!(gate_mode.data, gate_mode_data::AUTO);
+    assert_eq!(controller.target_velocity(), 0.0);
+    // Streamed while the gate is still EXTERNAL
+    let speeds: Vec<f32> = transport
+        .published("external/selected/control_cmd")
+        .iter()
+        .map(|payload| {
+            decode::<AckermannControlCommand>(payload)
+                .longitudinal
+                .speed
+        })
+        .collect();
+    assert!(speeds.iter().any(|&speed| speed > 0.0 && speed < 5.0));
+    assert_eq!(*speeds.last().unwrap(), 0.0);
+}
+
+#[test]
+fn new_targets_or_braking_cancel_the_handover() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    let mut settings = Settings::default();
+    settings.control.handover_ramp_time = 0.3;
+    controller.set_settings(settings);
+    controller.set_publish_policy(PublishPolicy::ExternalOnly);
+    controller.init();
+    transport.inject_msg(
+        "control/current_gate_mode",
+        &GateMode {
+            data: gate_mode_data::EXTERNAL,
+        },
+    );
+    thread::sleep(Duration::from_millis(100));
+
+    assert!(!controller.toggle_gate_mode());
+    controller.update_control_command(2.0, 0.0);
+    assert!(!controller.handing_over());
+
+    assert!(!controller.toggle_gate_mode());
+    controller.set_brake(1.0);
+    thread::sleep(Duration::from_millis(100));
+    assert!(!controller.handing_over());
+    controller.set_brake(0.0);
+
+    // Toggling again stays in EXTERNAL
+    assert!(!controller.toggle_gate_mode());
+    assert!(controller.toggle_gate_mode());
+    assert!(!controller.handing_over());
+    thread::sleep(Duration::from_millis(500));
+    assert!(transport
+        .last_published::<GateMode>(&controller.topics().gate_mode_cmd)
+        .is_none());
+    assert_eq!(controller.target_velocity(), 2.0);
+}
+
+#[test]
+fn cruise_holds_reported_speed_until_cancelled() {
+    let (transport, mut controller) = new_controller(Mode::ROS2);
+    controller.init();
+    let gear = |command| GearCommand {
+        stamp: Time { sec: 0, nanosec: 0 },
+        command,
+    };
+    transport.inject_msg("vehicle/status/gear_status", &gear(gear_command::DRIVE));
+    transport.inject_msg(
+        "vehicle/status/velocity_status",
+        &VelocityReport {
+            header: Header {
+                stamp: Time { sec: 0, nanosec: 0 },
+                frame_id: String::new(),
+            },
+            longitudinal_velocity: 4.0,
+            lateral_velocity: 0.0,
+            heading_rate: 0.0,
+        },
+    );
+
+    assert_eq!(controller.set_cruise(None), 4.0);
+    assert_eq!(controller.cruise(), CruiseState::Active { target: 4.0 });
+    assert!(controller.get_status().contains("Cruise:14.4 km/hr"));
+    thread::sleep(Duration::from_millis(100));
+    let cmd: AckermannControlCommand = transport
+        .last_published("external/selected/control_cmd")
+        .unwrap();
+    assert_eq!(cmd.longitudinal.speed, 4.0);
+
+    // Braking cancels
+    controller.update_control_command(0.0, 0.0);
+    assert_eq!(controller.cruise(), CruiseState::Standby { target: 4.0 });
+    assert!(controller.resume_cruise());
+    assert_eq!(controller.target_velocity(), 4.0);
+
+    // So does a gear change reported by the vehicle
+    transport.inject_msg("vehicle/status/gear_status", &gear(gear_command::REVERSE));
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(controller.cruise(), CruiseState::Standby { target: 4.0 });
+    assert_eq!(controller.target_velocity(), 0.0);
+}
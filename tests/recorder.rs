@@ -0,0 +1,57 @@
+use autoware_manual_control::{
+    mcap::McapReader, ManualController, MockTransport, Mode, Recorder, RecordingTransport, Topics,
+};
+use std::sync::Arc;
+use zenoh_ros_type::{autoware_auto_vehicle_msgs::gear_command, tier4_control_msgs::GateMode};
+
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle.as_bytes())
+}
+
+#[test]
+fn commands_and_status_are_recorded() {
+    let path = std::env::temp_dir().join(format!("recorder-{}.mcap", std::process::id()));
+    let topics = Topics::new(Mode::ROS2, "");
+    let recorder = Arc::new(Recorder::create(&path, &topics).unwrap());
+    let mock = Arc::new(MockTransport::new());
+    let transport = Arc::new(RecordingTransport::new(mock.clone(), recorder.clone()));
+    let mut controller = ManualController::with_transport(transport, Mode::ROS2, String::new());
+    controller.init();
+    controller.stop();
+
+    controller.pub_gear_command(gear_command::DRIVE);
+    // No engage service: only the request is recorded
+    assert!(controller.engage().is_err());
+    mock.inject_msg(&topics.current_gate_mode, &GateMode { data: 1 });
+    recorder.finish().unwrap();
+    // Finished recorders ignore later samples
+    controller.pub_gear_command(gear_command::PARK);
+
+    let file = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(file.starts_with(b"\x89MCAP0\r\n") && file.ends_with(b"\x89MCAP0\r\n"));
+    assert!(contains(&file, "/external/selected/gear_cmd"));
+    assert!(contains(
+        &file,
+        "autoware_auto_vehicle_msgs/msg/GearCommand"
+    ));
+    assert!(contains(&file, "/control/current_gate_mode"));
+    assert!(contains(&file, "zenoh_key"));
+    assert!(!contains(&file, "/vehicle/status/velocity_status"));
+
+    // The "ros2" profile needs a schema for every cdr channel
+    let reader = McapReader::parse(&file).unwrap();
+    let engage = reader
+        .channels
+        .values()
+        .find(|channel| channel.topic == "/api/autoware/set/engage")
+        .unwrap();
+    assert_eq!(engage.schema_id, 0);
+    assert_ne!(engage.message_encoding, "cdr");
+    assert!(reader
+        .channels
+        .values()
+        .all(|channel| channel.schema_id != 0 || channel.message_encoding != "cdr"));
+}
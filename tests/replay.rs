@@ -0,0 +1,240 @@
+use autoware_manual_control::{
+    replay::RecordedCommand, sim::Simulator, ManualController, MockTransport, Mode, Recorder,
+    Recording, RecordingTransport, ReplayOptions, ReplayState, Replayer, Settings, Topics,
+};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use zenoh_ros_type::{
+    autoware_auto_control_msgs::AckermannControlCommand, autoware_auto_vehicle_msgs::gear_command,
+    tier4_control_msgs::gate_mode_data,
+};
+
+/// Drive the simulator forward while turning left, and return the recording.
+fn record_drive(mode: Mode) -> Recording {
+    let path = std::env::temp_dir().join(format!("replay-{}-{}.mcap", mode, std::process::id()));
+    let topics = Topics::new(mode, "");
+    let recorder = Arc::new(Recorder::create(&path, &topics).unwrap());
+    let mock = Arc::new(MockTransport::new());
+    let mut simulator = Simulator::new(
+        mock.clone(),
+        mode,
+        String::new(),
+        Settings::default().vehicle.wheelbase,
+    );
+    let transport = Arc::new(RecordingTransport::new(mock, recorder.clone()));
+    let mut controller = ManualController::with_transport(transport, mode, String::new());
+    simulator.start();
+    controller.init();
+
+    assert!(controller.toggle_gate_mode());
+    controller.pub_gear_command(gear_command::DRIVE);
+    controller.update_control_command(5.0, 0.1);
+    thread::sleep(Duration::from_millis(500));
+    controller.stop();
+    recorder.finish().unwrap();
+
+    let recording = Recording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    recording
+}
+
+fn replay_drive(mode: Mode) {
+    let recording = record_drive(mode);
+    assert!(recording.duration() >= Duration::from_millis(400));
+
+    let mock = Arc::new(MockTransport::new());
+    let mut simulator = Simulator::new(
+        mock.clone(),
+        mode,
+        String::new(),
+        Settings::default().vehicle.wheelbase,
+    );
+    let mut controller = ManualController::with_transport(mock.clone(), mode, String::new());
+    simulator.start();
+    controller.init();
+    controller.pause();
+
+    let options = ReplayOptions {
+        speed: 2.0,
+        ..Default::default()
+    };
+    let mut replayer = Replayer::start(
+        recording,
+        mock,
+        Topics::new(mode, ""),
+        controller.status_tracker(),
+        options,
+    )
+    .unwrap();
+    assert_eq!(replayer.wait(), ReplayState::Finished);
+
+    let state = simulator.state();
+    assert!(state.engage);
+    assert_eq!(state.gate_mode, gate_mode_data::EXTERNAL);
+    assert_eq!(state.gear, gear_command::DRIVE);
+    assert!(state.x > 0.0 && state.yaw > 0.0);
+}
+
+#[test]
+fn replay_drive_over_ros2_keys() {
+    replay_drive(Mode::ROS2);
+}
+
+#[test]
+fn replay_drive_over_dds_keys() {
+    replay_drive(Mode::DDS);
+}
+
+#[test]
+fn replay_holds_the_speed_limits() {
+    let recording = record_drive(Mode::ROS2);
+    let mock = Arc::new(MockTransport::new());
+    let mut simulator = Simulator::new(
+        mock.clone(),
+        Mode::ROS2,
+        String::new(),
+        Settings::default().vehicle.wheelbase,
+    );
+    let mut controller = ManualController::with_transport(mock.clone(), Mode::ROS2, String::new());
+    simulator.start();
+    controller.init();
+    controller.pause();
+
+    let options = ReplayOptions {
+        speed: 2.0,
+        speed_cap: Some(1.0),
+        ..Default::default()
+    };
+    let topics = Topics::new(Mode::ROS2, "");
+    let mut replayer = Replayer::start(
+        recording,
+        mock.clone(),
+        topics.clone(),
+        controller.status_tracker(),
+        options,
+    )
+    .unwrap();
+    assert_eq!(replayer.wait(), ReplayState::Finished);
+
+    let commands = mock.published(&topics.control_cmd);
+    assert!(!commands.is_empty());
+    for payload in commands {
+        let command: AckermannControlCommand =
+            cdr::deserialize_from(&*payload, cdr::size::Infinite).unwrap();
+        assert!(command.longitudinal.speed <= 1.0);
+    }
+}
+
+#[test]
+fn replay_aborts_without_telemetry() {
+    let recording = record_drive(Mode::ROS2);
+    let mock = Arc::new(MockTransport::new());
+    let controller = ManualController::with_transport(mock.clone(), Mode::ROS2, String::new());
+    let mut replayer = Replayer::start(
+        recording,
+        mock,
+        Topics::new(Mode::ROS2, ""),
+        controller.status_tracker(),
+        ReplayOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        replayer.wait(),
+        ReplayState::Aborted("No vehicle telemetry".to_owned())
+    );
+}
+
+#[test]
+fn replay_can_be_paused_and_aborted() {
+    let recording = record_drive(Mode::ROS2);
+    let mock = Arc::new(MockTransport::new());
+    let mut simulator = Simulator::new(
+        mock.clone(),
+        Mode::ROS2,
+        String::new(),
+        Settings::default().vehicle.wheelbase,
+    );
+    let mut controller = ManualController::with_transport(mock.clone(), Mode::ROS2, String::new());
+    simulator.start();
+    controller.init();
+    controller.pause();
+
+    let mut replayer = Replayer::start(
+        recording,
+        mock,
+        Topics::new(Mode::ROS2, ""),
+        controller.status_tracker(),
+        ReplayOptions::default(),
+    )
+    .unwrap();
+    replayer.pause();
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(replayer.state(), ReplayState::Paused);
+    assert_eq!(simulator.state().velocity, 0.0);
+
+    replayer.abort();
+    assert_eq!(
+        replayer.wait(),
+        ReplayState::Aborted("Aborted by the operator".to_owned())
+    );
+}
+
+#[test]
+fn replay_rejects_invalid_speeds() {
+    let mock = Arc::new(MockTransport::new());
+    let controller = ManualController::with_transport(mock.clone(), Mode::ROS2, String::new());
+    for speed in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+        let options = ReplayOptions {
+            speed,
+            ..Default::default()
+        };
+        assert!(Replayer::start(
+            Recording::default(),
+            mock.clone(),
+            Topics::new(Mode::ROS2, ""),
+            controller.status_tracker(),
+            options,
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn replay_counts_the_skipped_samples() {
+    let command = |topic: &str, direction: &str| RecordedCommand {
+        topic: topic.to_owned(),
+        direction: direction.to_owned(),
+        offset: Duration::ZERO,
+        payload: Vec::new(),
+    };
+    let recording = Recording {
+        commands: vec![
+            command("/api/routing/clear_route", "request"),
+            command("/external/local/control_cmd", "command"),
+            command("/external/local/control_cmd", "command"),
+        ],
+    };
+    let mock = Arc::new(MockTransport::new());
+    let mut simulator = Simulator::new(
+        mock.clone(),
+        Mode::ROS2,
+        String::new(),
+        Settings::default().vehicle.wheelbase,
+    );
+    let mut controller = ManualController::with_transport(mock.clone(), Mode::ROS2, String::new());
+    simulator.start();
+    controller.init();
+    controller.pause();
+
+    let mut replayer = Replayer::start(
+        recording,
+        mock,
+        Topics::new(Mode::ROS2, ""),
+        controller.status_tracker(),
+        ReplayOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(replayer.wait(), ReplayState::Finished);
+    assert_eq!(replayer.skipped(), 3);
+}
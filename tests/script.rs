@@ -0,0 +1,130 @@
+use autoware_manual_control::{
+    sim::Simulator, ManualController, MockTransport, Mode, PublishPolicy, Script, Settings,
+};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use zenoh_ros_type::{
+    autoware_auto_control_msgs::AckermannControlCommand,
+    autoware_auto_vehicle_msgs::{gear_command, GearCommand, VelocityReport},
+    builtin_interfaces::Time,
+    std_msgs::Header,
+    tier4_control_msgs::{gate_mode_data, GateMode},
+};
+
+fn controller_with_simulator() -> (Simulator, ManualController) {
+    let transport = Arc::new(MockTransport::new());
+    let mut simulator = Simulator::new(
+        transport.clone(),
+        Mode::ROS2,
+        String::new(),
+        Settings::default().vehicle.wheelbase,
+    );
+    let mut controller = ManualController::with_transport(transport, Mode::ROS2, String::new());
+    simulator.start();
+    controller.init();
+    (simulator, controller)
+}
+
+#[test]
+fn maneuver_runs_to_completion() {
+    let (simulator, controller) = controller_with_simulator();
+    let script = Script::from_yaml(
+        "
+steps:
+  - engage
+  - gear: drive
+  - speed: { target: 5, over: 0.3 }
+  - steer: { angle: 10, hold: 0.3 }
+  - park
+timeout: 5
+",
+    )
+    .unwrap();
+
+    script.run(&controller, &AtomicBool::new(false)).unwrap();
+    let state = simulator.state();
+    assert_eq!(state.gate_mode, gate_mode_data::EXTERNAL);
+    assert_eq!(state.gear, gear_command::PARK);
+    assert!(state.velocity.abs() < 0.3);
+    assert!(state.x > 0.0 && state.yaw > 0.0);
+}
+
+#[test]
+fn maneuver_can_be_aborted() {
+    let (_simulator, controller) = controller_with_simulator();
+    let script = Script::from_yaml("steps: [engage, { hold: 10 }]").unwrap();
+    assert_eq!(
+        script.run(&controller, &AtomicBool::new(true)),
+        Err("Aborted by the operator".to_owned())
+    );
+}
+
+#[test]
+fn maneuver_times_out_without_vehicle() {
+    let controller =
+        ManualController::with_transport(Arc::new(MockTransport::new()), Mode::ROS2, String::new());
+    let script = Script::from_yaml("steps: [{ gear: drive }]\ntimeout: 0.2").unwrap();
+    assert_eq!(
+        script.run(&controller, &AtomicBool::new(false)),
+        Err("Timeout waiting for the gear".to_owned())
+    );
+}
+
+#[test]
+fn sample_maneuver_file_is_valid() {
+    let script = Script::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/maneuver.yaml")).unwrap();
+    assert!(!script.steps.is_empty());
+}
+
+#[test]
+fn speed_step_starts_from_the_takeover_targets() {
+    let transport = Arc::new(MockTransport::new());
+    let mut controller =
+        ManualController::with_transport(transport.clone(), Mode::ROS2, String::new());
+    controller.set_publish_policy(PublishPolicy::ExternalOnly);
+    controller.init();
+    transport.inject_msg(
+        "vehicle/status/gear_status",
+        &GearCommand {
+            stamp: Time { sec: 0, nanosec: 0 },
+            command: gear_command::DRIVE,
+        },
+    );
+    transport.inject_msg(
+        "vehicle/status/velocity_status",
+        &VelocityReport {
+            header: Header {
+                stamp: Time { sec: 0, nanosec: 0 },
+                frame_id: String::new(),
+            },
+            longitudinal_velocity: 8.0,
+            lateral_velocity: 0.0,
+            heading_rate: 0.0,
+        },
+    );
+    assert!(controller.toggle_gate_mode());
+    transport.inject_msg(
+        "control/current_gate_mode",
+        &GateMode {
+            data: gate_mode_data::EXTERNAL,
+        },
+    );
+
+    // 36 km/h is 10 m/s: the ramp goes from 8 to 10 m/s, not from 0
+    let script =
+        Script::from_yaml("steps: [{ speed: { target: 36, over: 0.3 } }]\ntimeout: 0.2").unwrap();
+    assert!(script.run(&controller, &AtomicBool::new(false)).is_err());
+    let speeds: Vec<f32> = transport
+        .published("external/selected/control_cmd")
+        .iter()
+        .map(|payload| {
+            cdr::deserialize_from::<_, AckermannControlCommand, _>(&**payload, cdr::size::Infinite)
+                .unwrap()
+                .longitudinal
+                .speed
+        })
+        .filter(|&speed| speed > 0.0)
+        .collect();
+    assert!(!speeds.is_empty());
+    assert!(speeds.iter().all(|&speed| speed >= 8.0));
+}
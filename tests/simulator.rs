@@ -0,0 +1,91 @@
+use autoware_manual_control::{
+    sim::Simulator, ManualController, MessageSet, MockTransport, Mode, Settings, Topics,
+};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use zenoh_ros_type::{
+    autoware_auto_vehicle_msgs::gear_command, tier4_control_msgs::gate_mode_data,
+};
+
+fn drive(mode: Mode, message_set: MessageSet) {
+    let transport = Arc::new(MockTransport::new());
+    let topics = Topics::new(mode, "").with_message_set(message_set);
+    let mut simulator = Simulator::with_topics(
+        transport.clone(),
+        topics,
+        Settings::default().vehicle.wheelbase,
+    );
+    let mut controller = ManualController::with_transport(transport, mode, String::new());
+    controller.set_message_set(message_set);
+    simulator.start();
+    controller.init();
+
+    assert!(controller.toggle_gate_mode());
+    controller.pub_gear_command(gear_command::DRIVE);
+    controller.update_control_command(5.0, 0.1);
+    thread::sleep(Duration::from_millis(500));
+
+    let state = simulator.state();
+    assert!(state.engage);
+    assert_eq!(state.gate_mode, gate_mode_data::EXTERNAL);
+    assert!(state.velocity > 0.0);
+    assert!(state.x > 0.0 && state.yaw > 0.0);
+
+    let status = controller.status();
+    assert!(status.engage);
+    assert_eq!(status.gate_mode, gate_mode_data::EXTERNAL);
+    assert_eq!(status.gear, gear_command::DRIVE);
+    assert!(status.current_velocity > 0.0);
+    assert!(status.steering_tire_angle > 0.0);
+    // Dead-reckoned pose follows the simulated one
+    assert!(status.pose.x > 0.0 && status.pose.yaw > 0.0);
+    assert!((status.pose.x - state.x).abs() < 1.0);
+    let path = controller.predicted_path(3.0);
+    assert!(path.last().unwrap().y > 0.0);
+}
+
+#[test]
+fn drive_simulator_over_ros2_keys() {
+    drive(Mode::ROS2, MessageSet::Auto);
+}
+
+#[test]
+fn drive_simulator_over_dds_keys() {
+    drive(Mode::DDS, MessageSet::Auto);
+}
+
+#[test]
+fn drive_simulator_with_autoware_control_msgs() {
+    drive(Mode::ROS2, MessageSet::New);
+}
+
+#[test]
+fn brake_slows_down_whatever_the_target() {
+    let transport = Arc::new(MockTransport::new());
+    let mut simulator = Simulator::new(
+        transport.clone(),
+        Mode::ROS2,
+        String::new(),
+        Settings::default().vehicle.wheelbase,
+    );
+    let mut controller = ManualController::with_transport(transport, Mode::ROS2, String::new());
+    simulator.start();
+    controller.init();
+    assert!(controller.toggle_gate_mode());
+    controller.pub_gear_command(gear_command::DRIVE);
+    controller.update_control_command(5.0, 0.0);
+    thread::sleep(Duration::from_millis(1000));
+    let cruising = simulator.state().velocity;
+    assert!(cruising > 1.0);
+
+    controller.set_brake(1.0);
+    thread::sleep(Duration::from_millis(300));
+    let braking = simulator.state().velocity;
+    assert!(braking < cruising - 0.5);
+    assert_eq!(controller.target_velocity(), 5.0);
+
+    controller.set_brake(0.0);
+    thread::sleep(Duration::from_millis(300));
+    assert!(simulator.state().velocity > braking);
+}